@@ -0,0 +1,879 @@
+// ---------- Checagem ----------
+
+// interpreta o programa com tipos no lugar dos valores pra achar stack vazia
+// e tipo errado antes de rodar, sem executar nada
+
+use std::collections::{HashMap, HashSet};
+
+use crate::{AST, ASTItem, Diagnostico, Efeito, Func, Item, No, Parametro, Pos, nome_builtin};
+
+// seguir citacoes que se chamam (?(dup !) dup !) nao termina nunca
+const PROFUNDIDADE_MAXIMA: usize = 64;
+
+#[derive(Debug, Clone)]
+enum Tipo {
+    Qualquer,
+    Bool,
+    Numero,
+    String,
+    // a citacao literal quando ela e conhecida, pra conseguir seguir o ! e o if
+    Funcao(Option<Func>),
+    Lista,
+    Palavra,
+    Erro,
+    Gerador,
+    Continuacao,
+    Canal,
+    // n-esima entrada da funcao sendo checada, 0 e a do topo
+    Entrada(usize),
+}
+
+impl Tipo {
+    fn do_nome(nome: &str) -> Tipo {
+        match nome {
+            "bool" => Tipo::Bool,
+            "numero" => Tipo::Numero,
+            "string" => Tipo::String,
+            "funcao" => Tipo::Funcao(None),
+            "lista" => Tipo::Lista,
+            "palavra" => Tipo::Palavra,
+            "erro" => Tipo::Erro,
+            "gerador" => Tipo::Gerador,
+            "continuacao" => Tipo::Continuacao,
+            "canal" => Tipo::Canal,
+            _ => Tipo::Qualquer,
+        }
+    }
+
+    fn do_item(item: &Item) -> Tipo {
+        match item {
+            Item::Func(f) => Tipo::Funcao(Some(f.clone())),
+            outro => Tipo::do_nome(outro.tipo()),
+        }
+    }
+
+    fn nome(&self) -> &'static str {
+        match self {
+            Tipo::Qualquer | Tipo::Entrada(_) => "qualquer",
+            Tipo::Bool => "bool",
+            Tipo::Numero => "numero",
+            Tipo::String => "string",
+            Tipo::Funcao(_) => "funcao",
+            Tipo::Lista => "lista",
+            Tipo::Palavra => "palavra",
+            Tipo::Erro => "erro",
+            Tipo::Gerador => "gerador",
+            Tipo::Continuacao => "continuacao",
+            Tipo::Canal => "canal",
+        }
+    }
+
+    fn mesmo(&self, outro: &Tipo) -> bool {
+        match (self, outro) {
+            (Tipo::Entrada(a), Tipo::Entrada(b)) => a == b,
+            (a, b) => std::mem::discriminant(a) == std::mem::discriminant(b),
+        }
+    }
+
+    fn juntar(&self, outro: &Tipo) -> Tipo {
+        match (self, outro) {
+            (Tipo::Funcao(_), Tipo::Funcao(_)) => Tipo::Funcao(None),
+            (a, b) if a.mesmo(b) => a.clone(),
+            _ => Tipo::Qualquer,
+        }
+    }
+}
+
+// efeito de uma funcao nomeada, visto de quem chama
+#[derive(Debug, Clone)]
+enum Assinatura {
+    // entradas a partir do topo, saidas de baixo pra cima podendo repetir entradas
+    Conhecida {
+        entradas: Vec<Tipo>,
+        saidas: Vec<Tipo>,
+    },
+    // usa eval, continuacao, ! de citacao desconhecida...
+    Desconhecida,
+    // sempre lanca um erro ou se chama pra sempre
+    Nunca,
+}
+
+impl Assinatura {
+    fn declarada(efeito: &Efeito) -> Assinatura {
+        let n = efeito.entradas.len();
+        let tipo = |tipo: &Option<String>| tipo.as_deref().map_or(Tipo::Qualquer, Tipo::do_nome);
+        let entradas = efeito
+            .entradas
+            .iter()
+            .rev()
+            .map(|p| tipo(&p.tipo))
+            .collect();
+        let saidas = efeito
+            .saidas
+            .iter()
+            .map(|p| {
+                // saida com o mesmo nome de uma entrada e aquela entrada
+                match efeito.entradas.iter().rposition(|e| e.nome == p.nome) {
+                    Some(i) if p.tipo.is_none() => Tipo::Entrada(n - 1 - i),
+                    _ => tipo(&p.tipo),
+                }
+            })
+            .collect();
+        Assinatura::Conhecida { entradas, saidas }
+    }
+
+    // de volta pra sintaxe do efeito, as entradas viram a b c... de baixo pra cima
+    fn efeito(&self) -> Option<Efeito> {
+        let Assinatura::Conhecida { entradas, saidas } = self else {
+            return None;
+        };
+        let n = entradas.len();
+        let parametro = |i: usize, tipo: &Tipo| Parametro {
+            nome: match i {
+                0..26 => ((b'a' + i as u8) as char).to_string(),
+                _ => format!("x{}", i),
+            },
+            tipo: match tipo {
+                Tipo::Qualquer | Tipo::Entrada(_) => None,
+                outro => Some(outro.nome().to_owned()),
+            },
+        };
+        Some(Efeito {
+            entradas: (0..n).map(|i| parametro(i, &entradas[n - 1 - i])).collect(),
+            saidas: saidas
+                .iter()
+                .enumerate()
+                .map(|(j, tipo)| match tipo {
+                    Tipo::Entrada(i) => parametro(n - 1 - i, tipo),
+                    outro => parametro(n + j, outro),
+                })
+                .collect(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct Abstrato {
+    stack: Vec<Tipo>,
+    // tipo exigido de cada entrada, 0 e a do topo
+    entradas: Vec<Tipo>,
+    // sem entradas: tirar da stack vazia e erro (main, gen, spawn, efeito declarado)
+    fechado: bool,
+    // depois de algo com efeito desconhecido nao da mais pra contar a stack
+    perdido: bool,
+}
+
+impl Abstrato {
+    fn fechado() -> Abstrato {
+        Abstrato {
+            fechado: true,
+            ..Abstrato::default()
+        }
+    }
+
+    fn perder(mut self) -> Abstrato {
+        self.perdido = true;
+        self.stack.clear();
+        self
+    }
+
+    fn concreto(&self, tipo: &Tipo) -> Tipo {
+        match tipo {
+            Tipo::Entrada(i) => self.entradas[*i].clone(),
+            outro => outro.clone(),
+        }
+    }
+
+    // poe as entradas ainda nao usadas embaixo da stack ate ela ter tam itens
+    fn completar(&mut self, tam: usize) {
+        while self.stack.len() < tam {
+            let tipo = if self.perdido {
+                Tipo::Qualquer
+            } else {
+                self.entradas.push(Tipo::Qualquer);
+                Tipo::Entrada(self.entradas.len() - 1)
+            };
+            self.stack.insert(0, tipo);
+        }
+    }
+}
+
+// `None` no lugar de um Abstrato: a execucao nunca chega ali
+type Fluxo = Option<Abstrato>;
+
+struct Checador<'a> {
+    corpos: HashMap<&'a str, &'a Func>,
+    declarados: HashMap<&'a str, (&'a Efeito, &'a Pos)>,
+    assinaturas: HashMap<String, Assinatura>,
+    // funcoes sendo inferidas, da de fora pra de dentro
+    em_andamento: Vec<String>,
+    // a mais de fora que uma recursao achou em andamento: o que foi inferido
+    // dentro dela ainda depende do resultado dela e nao vai pro cache
+    recursao: Option<usize>,
+    diagnosticos: Vec<Diagnostico>,
+    // dentro do corpo de um try o erro pode ser tratado, entao so avisa
+    em_try: usize,
+    profundidade: usize,
+}
+
+// infere o efeito de cada definicao e aponta onde a stack com certeza vai
+// estar vazia ou com o tipo errado
+pub fn checar(ast: &AST, funcoes: &HashMap<String, Func>) -> Vec<Diagnostico> {
+    let mut checador = Checador::new(ast, funcoes);
+    for definicao in ast {
+        checador.assinatura(&definicao.nome);
+    }
+
+    // a mesma citacao pode ser seguida mais de uma vez
+    let mut vistos = HashSet::new();
+    let mut diagnosticos: Vec<Diagnostico> = checador
+        .diagnosticos
+        .into_iter()
+        .filter(|d| vistos.insert((d.pos.as_ref().map(Pos::to_string), d.mensagem.clone())))
+        .collect();
+    diagnosticos.sort_by_key(|d| d.pos.as_ref().map(|p| (p.linha, p.coluna)));
+    diagnosticos
+}
+
+// o efeito que a funcao teria se fosse declarada, pro hover do lsp
+pub fn efeito_inferido(ast: &AST, funcoes: &HashMap<String, Func>, nome: &str) -> Option<Efeito> {
+    Checador::new(ast, funcoes).assinatura(nome).efeito()
+}
+
+fn nome_no(no: &No) -> &str {
+    match &no.item {
+        ASTItem::FuncCallNamed(nome) => nome,
+        item => nome_builtin(item).unwrap_or("?"),
+    }
+}
+
+impl<'a> Checador<'a> {
+    fn new(ast: &'a AST, funcoes: &'a HashMap<String, Func>) -> Checador<'a> {
+        let mut checador = Checador {
+            corpos: HashMap::new(),
+            declarados: HashMap::new(),
+            assinaturas: HashMap::new(),
+            em_andamento: vec![],
+            recursao: None,
+            diagnosticos: vec![],
+            em_try: 0,
+            profundidade: 0,
+        };
+        for (nome, corpo) in funcoes {
+            checador.corpos.insert(nome, corpo);
+        }
+        for definicao in ast {
+            checador.corpos.insert(&definicao.nome, &definicao.corpo);
+            match &definicao.efeito {
+                Some(efeito) => checador
+                    .declarados
+                    .insert(&definicao.nome, (efeito, &definicao.pos)),
+                None => checador.declarados.remove(definicao.nome.as_str()),
+            };
+        }
+        checador
+    }
+
+    fn reportar(&mut self, mensagem: String, pos: Option<Pos>) {
+        let diagnostico = if self.em_try > 0 {
+            Diagnostico::aviso(format!("{} (dentro de um try)", mensagem), pos)
+        } else {
+            Diagnostico::erro(mensagem, pos)
+        };
+        self.diagnosticos.push(diagnostico);
+    }
+
+    fn assinatura(&mut self, nome: &str) -> Assinatura {
+        if let Some(assinatura) = self.assinaturas.get(nome) {
+            return assinatura.clone();
+        }
+        let declarada = self
+            .declarados
+            .get(nome)
+            .map(|(efeito, _)| Assinatura::declarada(efeito));
+        if let Some(nivel) = self.em_andamento.iter().position(|n| n == nome) {
+            // recursao: sem efeito declarado o ramo que chama fica de fora
+            if declarada.is_none() {
+                self.recursao = Some(self.recursao.map_or(nivel, |r| r.min(nivel)));
+            }
+            return declarada.unwrap_or(Assinatura::Nunca);
+        }
+        let Some(corpo) = self.corpos.get(nome).copied() else {
+            return Assinatura::Desconhecida;
+        };
+        let nivel = self.em_andamento.len();
+        self.em_andamento.push(nome.to_owned());
+        let recursao = self.recursao.take();
+        let em_try = std::mem::take(&mut self.em_try);
+        let inferida = self.inferir(nome, corpo);
+        self.em_try = em_try;
+        self.em_andamento.pop();
+        // se chamar a si mesma o resultado ja e o final, se chamar uma de fora nao
+        let parcial = self.recursao.filter(|&r| r < nivel);
+        self.recursao = recursao.into_iter().chain(parcial).min();
+        let assinatura = declarada.unwrap_or(inferida);
+        if parcial.is_none() || self.declarados.contains_key(nome) {
+            self.assinaturas.insert(nome.to_owned(), assinatura.clone());
+        }
+        assinatura
+    }
+
+    fn inferir(&mut self, nome: &str, corpo: &Func) -> Assinatura {
+        let declarado = self.declarados.get(nome).copied();
+        let inicio = match declarado {
+            Some((efeito, _)) => {
+                let Assinatura::Conhecida { entradas, .. } = Assinatura::declarada(efeito) else {
+                    unreachable!()
+                };
+                Abstrato {
+                    stack: (0..entradas.len()).rev().map(Tipo::Entrada).collect(),
+                    entradas,
+                    ..Abstrato::fechado()
+                }
+            }
+            None if nome == "main" => Abstrato::fechado(),
+            None => Abstrato::default(),
+        };
+        let Some(fim) = self.seguir(corpo, Some(inicio)) else {
+            return Assinatura::Nunca;
+        };
+        if fim.perdido {
+            return Assinatura::Desconhecida;
+        }
+        if let Some((efeito, pos)) = declarado {
+            self.conferir_declarado(nome, efeito, pos, &fim);
+        }
+        Assinatura::Conhecida {
+            entradas: fim.entradas,
+            saidas: fim.stack,
+        }
+    }
+
+    fn conferir_declarado(&mut self, nome: &str, efeito: &Efeito, pos: &Pos, fim: &Abstrato) {
+        if fim.stack.len() != efeito.saidas.len() {
+            self.reportar(
+                format!(
+                    "`{}` declara {} mas deixa {} item(s) na stack",
+                    nome,
+                    efeito,
+                    fim.stack.len()
+                ),
+                Some(pos.clone()),
+            );
+            return;
+        }
+        for (parametro, tipo) in efeito.saidas.iter().zip(&fim.stack) {
+            let Some(declarado) = &parametro.tipo else {
+                continue;
+            };
+            let tipo = fim.concreto(tipo);
+            if !matches!(tipo, Tipo::Qualquer) && tipo.nome() != declarado {
+                self.reportar(
+                    format!(
+                        "`{}` declara a saida `{}` como {} mas deixa {}",
+                        nome,
+                        parametro.nome,
+                        declarado,
+                        tipo.nome()
+                    ),
+                    Some(pos.clone()),
+                );
+            }
+        }
+    }
+
+    fn seguir(&mut self, func: &Func, fluxo: Fluxo) -> Fluxo {
+        if self.profundidade > PROFUNDIDADE_MAXIMA {
+            return fluxo.map(Abstrato::perder);
+        }
+        self.profundidade += 1;
+        let mut fluxo = fluxo;
+        let mut anterior = None;
+        for no in func {
+            let Some(estado) = fluxo else {
+                break;
+            };
+            fluxo = self.passo(estado, no, anterior);
+            anterior = Some(&no.item);
+        }
+        self.profundidade -= 1;
+        fluxo
+    }
+
+    // codigo isolado com a stack propria, como em gen e spawn
+    fn isolado(&mut self, func: &Func) {
+        let em_try = std::mem::take(&mut self.em_try);
+        self.seguir(func, Some(Abstrato::fechado()));
+        self.em_try = em_try;
+    }
+
+    fn tirar(&mut self, estado: &mut Abstrato, no: &No) -> Option<Tipo> {
+        if let Some(tipo) = estado.stack.pop() {
+            return Some(tipo);
+        }
+        if estado.perdido {
+            return Some(Tipo::Qualquer);
+        }
+        if estado.fechado {
+            let mensagem = if estado.entradas.is_empty() {
+                format!("`{}` vai encontrar a stack vazia", nome_no(no))
+            } else {
+                format!("`{}` usa mais itens do que o efeito declarado", nome_no(no))
+            };
+            self.reportar(mensagem, no.pos.as_deref().cloned());
+            return None;
+        }
+        estado.entradas.push(Tipo::Qualquer);
+        Some(Tipo::Entrada(estado.entradas.len() - 1))
+    }
+
+    // exige um dos tipos, a entrada que ainda nao tinha tipo passa a ter
+    fn exigir(
+        &mut self,
+        estado: &mut Abstrato,
+        tipo: &Tipo,
+        aceitos: &[Tipo],
+        no: &No,
+    ) -> Option<()> {
+        let atual = estado.concreto(tipo);
+        if matches!(atual, Tipo::Qualquer) {
+            if let (Tipo::Entrada(i), [unico]) = (tipo, aceitos) {
+                estado.entradas[*i] = unico.clone();
+            }
+            return Some(());
+        }
+        if aceitos.iter().any(|aceito| aceito.mesmo(&atual)) {
+            return Some(());
+        }
+        let esperado: Vec<&str> = aceitos.iter().map(Tipo::nome).collect();
+        self.reportar(
+            format!(
+                "`{}` esperava {} mas vai receber {}",
+                nome_no(no),
+                esperado.join(" ou "),
+                atual.nome()
+            ),
+            no.pos.as_deref().cloned(),
+        );
+        None
+    }
+
+    fn tirar_tipo(&mut self, estado: &mut Abstrato, aceitos: &[Tipo], no: &No) -> Option<Tipo> {
+        let tipo = self.tirar(estado, no)?;
+        self.exigir(estado, &tipo, aceitos, no)?;
+        Some(estado.concreto(&tipo))
+    }
+
+    fn juntar(&mut self, a: Fluxo, b: Fluxo, no: &No) -> Fluxo {
+        let (mut a, mut b) = match (a, b) {
+            (None, fluxo) | (fluxo, None) => return fluxo,
+            (Some(a), Some(b)) => (a, b),
+        };
+        let entradas = a.entradas.len().max(b.entradas.len());
+        for estado in [&mut a, &mut b] {
+            let tam = estado.stack.len() + entradas - estado.entradas.len();
+            estado.completar(tam);
+        }
+        a.entradas = a
+            .entradas
+            .iter()
+            .zip(&b.entradas)
+            .map(|(x, y)| x.juntar(y))
+            .collect();
+        if a.perdido || b.perdido {
+            return Some(a.perder());
+        }
+        if a.stack.len() != b.stack.len() {
+            self.diagnosticos.push(Diagnostico::aviso(
+                format!(
+                    "os dois lados do `{}` deixam a stack com tamanhos diferentes ({} e {})",
+                    nome_no(no),
+                    a.stack.len(),
+                    b.stack.len()
+                ),
+                no.pos.as_deref().cloned(),
+            ));
+            return Some(a.perder());
+        }
+        a.stack = a
+            .stack
+            .iter()
+            .zip(&b.stack)
+            .map(|(x, y)| x.juntar(y))
+            .collect();
+        Some(a)
+    }
+
+    fn passo(&mut self, mut estado: Abstrato, no: &No, anterior: Option<&ASTItem>) -> Fluxo {
+        use Tipo::*;
+        let funcao = || Funcao(None);
+        match &no.item {
+            ASTItem::True | ASTItem::False => estado.stack.push(Bool),
+            ASTItem::Numero(_) | ASTItem::SSize => estado.stack.push(Numero),
+            ASTItem::String(_) => estado.stack.push(String),
+            ASTItem::Valor(item) => estado.stack.push(Tipo::do_item(item)),
+            ASTItem::FuncDef(f) => estado.stack.push(Funcao(Some(f.clone()))),
+            ASTItem::Chan => estado.stack.push(Canal),
+            ASTItem::DebugS | ASTItem::Break => {}
+            ASTItem::Mais => {
+                self.tirar_tipo(&mut estado, &[Numero], no)?;
+                self.tirar_tipo(&mut estado, &[Numero], no)?;
+                estado.stack.push(Numero);
+            }
+            ASTItem::Maior | ASTItem::Menor => {
+                self.tirar_tipo(&mut estado, &[Numero], no)?;
+                self.tirar_tipo(&mut estado, &[Numero], no)?;
+                estado.stack.extend([Numero, Numero, Bool]);
+            }
+            ASTItem::Print => {
+                let tipo = self.tirar(&mut estado, no)?;
+                estado.stack.push(tipo);
+            }
+            ASTItem::Input => {
+                self.tirar_tipo(&mut estado, &[String], no)?;
+                estado.stack.push(String);
+            }
+            ASTItem::Pop => {
+                // pop na stack vazia nao faz nada
+                if !(estado.stack.is_empty() && estado.fechado) {
+                    self.tirar(&mut estado, no)?;
+                }
+            }
+            ASTItem::Dup => {
+                let tipo = self.tirar(&mut estado, no)?;
+                estado.stack.extend([tipo.clone(), tipo]);
+            }
+            ASTItem::Swap => {
+                let a = self.tirar(&mut estado, no)?;
+                let b = self.tirar(&mut estado, no)?;
+                estado.stack.extend([a, b]);
+            }
+            ASTItem::SwapN => {
+                self.tirar_tipo(&mut estado, &[Numero], no)?;
+                // so da pra seguir com a posicao escrita logo antes
+                let Some(ASTItem::Numero(n)) = anterior else {
+                    return Some(estado.perder());
+                };
+                if *n < 0 {
+                    self.reportar(
+                        format!("`swapn` com posicao negativa: {}", n),
+                        no.pos.as_deref().cloned(),
+                    );
+                    return None;
+                }
+                let n = *n as usize;
+                if n == 0 {
+                    return Some(estado);
+                }
+                if estado.fechado && !estado.perdido && estado.stack.len() <= n {
+                    self.reportar(
+                        format!(
+                            "`swapn` troca com a posicao {} mas a stack so vai ter {} item(s)",
+                            n,
+                            estado.stack.len()
+                        ),
+                        no.pos.as_deref().cloned(),
+                    );
+                    return None;
+                }
+                estado.completar(n + 1);
+                let topo = estado.stack.len() - 1;
+                estado.stack.swap(topo, topo - n);
+            }
+            ASTItem::If => {
+                let falso = self.tirar_tipo(&mut estado, &[funcao()], no)?;
+                let verdadeiro = self.tirar_tipo(&mut estado, &[funcao()], no)?;
+                self.tirar_tipo(&mut estado, &[Bool], no)?;
+                return match (verdadeiro, falso) {
+                    (Funcao(Some(v)), Funcao(Some(f))) => {
+                        let v = self.seguir(&v, Some(estado.clone()));
+                        let f = self.seguir(&f, Some(estado));
+                        self.juntar(v, f, no)
+                    }
+                    (Funcao(Some(f)), _) | (_, Funcao(Some(f))) => {
+                        self.seguir(&f, Some(estado.clone()));
+                        Some(estado.perder())
+                    }
+                    _ => Some(estado.perder()),
+                };
+            }
+            ASTItem::Curry | ASTItem::With => {
+                self.tirar_tipo(&mut estado, &[funcao()], no)?;
+                self.tirar(&mut estado, no)?;
+                estado.stack.push(funcao());
+            }
+            ASTItem::Curry2 => {
+                self.tirar_tipo(&mut estado, &[funcao()], no)?;
+                self.tirar(&mut estado, no)?;
+                self.tirar(&mut estado, no)?;
+                estado.stack.push(funcao());
+            }
+            ASTItem::Compose => {
+                let segunda = self.tirar_tipo(&mut estado, &[funcao()], no)?;
+                let primeira = self.tirar_tipo(&mut estado, &[funcao()], no)?;
+                estado.stack.push(match (primeira, segunda) {
+                    (Funcao(Some(mut a)), Funcao(Some(b))) => {
+                        a.extend(b);
+                        Funcao(Some(a))
+                    }
+                    _ => funcao(),
+                });
+            }
+            ASTItem::ToList => {
+                self.tirar_tipo(&mut estado, &[funcao()], no)?;
+                estado.stack.push(Lista);
+            }
+            ASTItem::ToQuot => {
+                self.tirar_tipo(&mut estado, &[Lista], no)?;
+                estado.stack.push(funcao());
+            }
+            ASTItem::Size => {
+                self.tirar_tipo(&mut estado, &[funcao(), Lista], no)?;
+                estado.stack.push(Numero);
+            }
+            ASTItem::First => {
+                self.tirar_tipo(&mut estado, &[funcao(), Lista], no)?;
+                estado.stack.push(Qualquer);
+            }
+            ASTItem::Rest | ASTItem::Append => {
+                if matches!(no.item, ASTItem::Append) {
+                    self.tirar(&mut estado, no)?;
+                }
+                let tipo = self.tirar_tipo(&mut estado, &[funcao(), Lista], no)?;
+                estado.stack.push(match tipo {
+                    Funcao(_) => funcao(),
+                    outro => outro,
+                });
+            }
+            ASTItem::Word => {
+                self.tirar_tipo(&mut estado, &[String], no)?;
+                estado.stack.push(Palavra);
+            }
+            ASTItem::Eval => {
+                // o codigo so existe em tempo de execucao
+                self.tirar_tipo(&mut estado, &[String], no)?;
+                return Some(estado.perder());
+            }
+            ASTItem::Define => {
+                self.tirar_tipo(&mut estado, &[funcao()], no)?;
+                self.tirar_tipo(&mut estado, &[String], no)?;
+                return Some(estado.perder());
+            }
+            ASTItem::Throw => {
+                self.tirar(&mut estado, no)?;
+                return None;
+            }
+            ASTItem::Try => {
+                let tratador = self.tirar_tipo(&mut estado, &[funcao()], no)?;
+                let corpo = self.tirar_tipo(&mut estado, &[funcao()], no)?;
+                let (Funcao(Some(corpo)), Funcao(Some(tratador))) = (corpo, tratador) else {
+                    return Some(estado.perder());
+                };
+                self.em_try += 1;
+                let fim_corpo = self.seguir(&corpo, Some(estado.clone()));
+                self.em_try -= 1;
+                estado.stack.push(Erro);
+                let fim_tratador = self.seguir(&tratador, Some(estado));
+                return self.juntar(fim_corpo, fim_tratador, no);
+            }
+            ASTItem::Error => {
+                self.tirar_tipo(&mut estado, &[String], no)?;
+                self.tirar_tipo(&mut estado, &[String], no)?;
+                estado.stack.push(Erro);
+            }
+            ASTItem::ErrKind | ASTItem::ErrMsg | ASTItem::ErrPos => {
+                self.tirar_tipo(&mut estado, &[Erro], no)?;
+                estado.stack.push(String);
+            }
+            ASTItem::Gen | ASTItem::Spawn => {
+                if let Funcao(Some(f)) = self.tirar_tipo(&mut estado, &[funcao()], no)? {
+                    self.isolado(&f);
+                }
+                if matches!(no.item, ASTItem::Gen) {
+                    estado.stack.push(Gerador);
+                }
+            }
+            ASTItem::Yield => {
+                self.tirar(&mut estado, no)?;
+            }
+            ASTItem::Next => {
+                // empilha valor e true ou so false
+                self.tirar_tipo(&mut estado, &[Gerador], no)?;
+                return Some(estado.perder());
+            }
+            ASTItem::Take => {
+                self.tirar_tipo(&mut estado, &[Numero], no)?;
+                self.tirar_tipo(&mut estado, &[Gerador, Lista], no)?;
+                estado.stack.push(Lista);
+            }
+            ASTItem::Map | ASTItem::Filter => {
+                self.tirar_tipo(&mut estado, &[funcao()], no)?;
+                let tipo = self.tirar_tipo(&mut estado, &[Gerador, Lista], no)?;
+                estado.stack.push(tipo);
+            }
+            ASTItem::Each => {
+                let f = self.tirar_tipo(&mut estado, &[funcao()], no)?;
+                self.tirar_tipo(&mut estado, &[Gerador, Lista], no)?;
+                let Funcao(Some(f)) = f else {
+                    return Some(estado.perder());
+                };
+                // roda zero ou mais vezes, entao so segue se cada vez consome o item
+                let mut iteracao = estado.clone();
+                iteracao.stack.push(Qualquer);
+                return match self.seguir(&f, Some(iteracao)) {
+                    Some(fim)
+                        if !fim.perdido
+                            && fim.stack.len() == estado.stack.len()
+                            && fim.entradas.len() == estado.entradas.len() =>
+                    {
+                        self.juntar(Some(estado), Some(fim), no)
+                    }
+                    None => Some(estado),
+                    Some(_) => Some(estado.perder()),
+                };
+            }
+            ASTItem::CallCC | ASTItem::CallCCS => {
+                self.tirar_tipo(&mut estado, &[funcao()], no)?;
+                return Some(estado.perder());
+            }
+            ASTItem::Send => {
+                self.tirar(&mut estado, no)?;
+                self.tirar_tipo(&mut estado, &[Canal], no)?;
+                estado.stack.push(Canal);
+            }
+            ASTItem::Recv => {
+                self.tirar_tipo(&mut estado, &[Canal], no)?;
+                estado.stack.extend([Canal, Qualquer]);
+            }
+            ASTItem::Assert => {
+                self.tirar_tipo(&mut estado, &[Bool], no)?;
+            }
+            ASTItem::AssertEq => {
+                self.tirar(&mut estado, no)?;
+                self.tirar(&mut estado, no)?;
+            }
+            // compara a stack toda mas nao mexe nela
+            ASTItem::AssertStack => {
+                self.tirar_tipo(&mut estado, &[funcao(), Lista], no)?;
+            }
+            ASTItem::FuncCallTop => {
+                return match self.tirar_tipo(&mut estado, &[funcao(), Continuacao], no)? {
+                    Funcao(Some(f)) => self.seguir(&f, Some(estado)),
+                    // continuacao nao volta pra ca
+                    Continuacao => None,
+                    _ => Some(estado.perder()),
+                };
+            }
+            ASTItem::FuncCallNamed(nome) => match self.assinatura(nome) {
+                Assinatura::Conhecida { entradas, saidas } => {
+                    let mut recebidos = vec![];
+                    for exigido in &entradas {
+                        let tipo = self.tirar(&mut estado, no)?;
+                        if !matches!(exigido, Qualquer) {
+                            self.exigir(&mut estado, &tipo, std::slice::from_ref(exigido), no)?;
+                        }
+                        recebidos.push(tipo);
+                    }
+                    estado
+                        .stack
+                        .extend(saidas.into_iter().map(|tipo| match tipo {
+                            Entrada(i) => recebidos[i].clone(),
+                            outro => outro,
+                        }));
+                }
+                Assinatura::Desconhecida => return Some(estado.perder()),
+                Assinatura::Nunca => return None,
+            },
+            // so aparecem na stack de consumo
+            ASTItem::FimTry(..) | ASTItem::FimChamada(_) | ASTItem::EachGerador(..) => {}
+        }
+        Some(estado)
+    }
+}
+
+#[cfg(test)]
+mod testes {
+    use super::*;
+    use crate::analisar;
+
+    fn inferido(fonte: &str, nome: &str) -> Option<String> {
+        let (ast, _) = analisar(fonte, "<teste>");
+        efeito_inferido(&ast, &HashMap::new(), nome).map(|efeito| efeito.to_string())
+    }
+
+    fn mensagens(fonte: &str) -> Vec<String> {
+        let (ast, _) = analisar(fonte, "<teste>");
+        checar(&ast, &HashMap::new())
+            .into_iter()
+            .map(|d| d.mensagem)
+            .collect()
+    }
+
+    #[test]
+    fn assinatura_conhecida() {
+        assert_eq!(
+            inferido("f = ( 1 + )", "f").as_deref(),
+            Some("( a:numero -- b:numero )")
+        );
+        assert_eq!(
+            inferido("f = ( swap )", "f").as_deref(),
+            Some("( a b -- b a )")
+        );
+    }
+
+    #[test]
+    fn assinatura_desconhecida() {
+        assert_eq!(inferido("f = ( \"1\" eval )", "f"), None);
+        // quem chama tambem perde a conta da stack
+        assert_eq!(inferido("f = ( \"1\" eval )\ng = ( f 1 )", "g"), None);
+    }
+
+    #[test]
+    fn assinatura_nunca() {
+        // o ramo que nunca volta fica de fora do if
+        let fonte = "f = ( \"x\" throw )\ng = ( ?( f ) ?( 1 ) if )";
+        assert_eq!(
+            inferido(fonte, "g").as_deref(),
+            Some("( a:bool -- b:numero )")
+        );
+        assert_eq!(
+            mensagens("f = ( \"x\" throw )\nmain = ( f + )"),
+            [] as [String; 0]
+        );
+    }
+
+    #[test]
+    fn juntar_ramos_do_if() {
+        assert_eq!(
+            inferido("f = ( ?( 1 ) ?( 2 ) if )", "f").as_deref(),
+            Some("( a:bool -- b:numero )")
+        );
+        assert_eq!(
+            inferido("f = ( ?( 1 ) ?( \"x\" ) if )", "f").as_deref(),
+            Some("( a:bool -- b )")
+        );
+        // o ramo que usa uma entrada a mais faz as duas pedirem ela
+        assert_eq!(
+            inferido("f = ( ?( 1 + ) ?( ) if )", "f").as_deref(),
+            Some("( a b:bool -- c )")
+        );
+        assert_eq!(
+            mensagens("main = ( true ?( 1 ) ?( 1 2 ) if )"),
+            ["os dois lados do `if` deixam a stack com tamanhos diferentes (1 e 2)"]
+        );
+    }
+
+    #[test]
+    fn recursao() {
+        assert_eq!(
+            inferido("f = ( ?( 1 ) ?( f ) if )", "f").as_deref(),
+            Some("( a:bool -- b:numero )")
+        );
+        // g e inferida no meio de f, mas f volta pelo outro ramo
+        let fonte = "f = ( ?( 1 ) ?( g ) if )\ng = ( f )\nmain = ( true g \"x\" + )";
+        assert_eq!(
+            mensagens(fonte),
+            ["`+` esperava numero mas vai receber string"]
+        );
+    }
+}
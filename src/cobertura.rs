@@ -0,0 +1,250 @@
+// ---------- Cobertura ----------
+
+// --coverage: quantas vezes cada no rodou e pra que lado cada if foi, por
+// linha de cada arquivo, num resumo no stderr e no formato lcov que os
+// visualizadores de cobertura leem
+//
+// exemplos/06.psf: linhas 5/5 (100.0%), citacoes 1/1, ramos 2/2, funcoes 3/3
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as _;
+use std::fs;
+use std::rc::Rc;
+
+use crate::{ASTItem, ErroPSF, Item, No, Observador, PSFState, Pos, Stack, analisar, visitar_nos};
+
+type Chave = (Rc<str>, usize, usize);
+
+fn chave(pos: &Pos) -> Chave {
+    (pos.arquivo.clone(), pos.linha, pos.coluna)
+}
+
+// junta varias execucoes, como as de cada teste
+#[derive(Debug, Default)]
+pub struct Cobertura {
+    nos: HashMap<Chave, u64>,
+    // vezes que cada if foi pro lado verdadeiro e pro falso
+    ramos: HashMap<Chave, [u64; 2]>,
+    // chamadas de cada funcao, que de corpo vazio nao tem no pra contar
+    chamadas: HashMap<String, u64>,
+}
+
+impl Observador for Rc<RefCell<Cobertura>> {
+    fn passo(
+        &mut self,
+        estado: &mut PSFState,
+        no: &No,
+        _stack_consumir: &Stack<No>,
+    ) -> Result<(), ErroPSF> {
+        let mut cobertura = self.borrow_mut();
+        if let ASTItem::FuncCallNamed(nome) = &no.item
+            && estado.funcoes.contains_key(nome)
+        {
+            *cobertura.chamadas.entry(nome.clone()).or_default() += 1;
+        }
+        let Some(pos) = &no.pos else {
+            return Ok(());
+        };
+        *cobertura.nos.entry(chave(pos)).or_default() += 1;
+        if let ASTItem::If = no.item {
+            // a condicao fica embaixo das duas citacoes
+            let lista = &estado.stack.lista;
+            if let Some(Item::Bool(b)) = lista.len().checked_sub(3).map(|i| &lista[i]) {
+                cobertura.ramos.entry(chave(pos)).or_default()[usize::from(!b)] += 1;
+            }
+        }
+        Ok(())
+    }
+}
+
+// o que tinha pra cobrir num arquivo e quanto foi
+#[derive(Debug, Default)]
+struct Medida {
+    // a linha conta as vezes do no que mais rodou nela
+    linhas: BTreeMap<usize, u64>,
+    // nome, linha e as vezes que foi chamada
+    funcoes: Vec<(String, usize, u64)>,
+    citacoes: usize,
+    citacoes_executadas: usize,
+    // linha do if e as vezes de cada lado, None se ele nunca rodou
+    ramos: Vec<(usize, Option<[u64; 2]>)>,
+}
+
+impl Medida {
+    fn linhas_cobertas(&self) -> usize {
+        self.linhas.values().filter(|&&vezes| vezes > 0).count()
+    }
+
+    fn ramos_cobertos(&self) -> usize {
+        self.ramos
+            .iter()
+            .filter_map(|(_, lados)| *lados)
+            .map(|lados| lados.iter().filter(|&&vezes| vezes > 0).count())
+            .sum()
+    }
+
+    fn funcoes_cobertas(&self) -> usize {
+        self.funcoes
+            .iter()
+            .filter(|(_, _, vezes)| *vezes > 0)
+            .count()
+    }
+}
+
+// 3 4 5 9 -> 3-5, 9
+fn intervalos(linhas: &[usize]) -> String {
+    let mut partes: Vec<(usize, usize)> = vec![];
+    for &linha in linhas {
+        match partes.last_mut() {
+            Some((_, fim)) if *fim + 1 == linha => *fim = linha,
+            _ => partes.push((linha, linha)),
+        }
+    }
+    partes
+        .iter()
+        .map(|&(comeco, fim)| match comeco == fim {
+            true => comeco.to_string(),
+            false => format!("{}-{}", comeco, fim),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn porcento(parte: usize, total: usize) -> f64 {
+    match total {
+        0 => 100.0,
+        _ => parte as f64 * 100.0 / total as f64,
+    }
+}
+
+impl Cobertura {
+    fn vezes(&self, pos: &Pos) -> u64 {
+        self.nos.get(&chave(pos)).copied().unwrap_or(0)
+    }
+
+    fn medir(&self, arquivo: &str, conteudo: &str) -> Medida {
+        let (ast, _) = analisar(conteudo, arquivo);
+        let mut medida = Medida::default();
+        for definicao in &ast {
+            let vezes = self.chamadas.get(&definicao.nome).copied().unwrap_or(0);
+            medida
+                .funcoes
+                .push((definicao.nome.clone(), definicao.pos.linha, vezes));
+            visitar_nos(&definicao.corpo, &mut |no| {
+                let Some(pos) = &no.pos else {
+                    return;
+                };
+                match &no.item {
+                    // empilhar a citacao nao e rodar o que tem dentro
+                    ASTItem::FuncDef(corpo) => {
+                        if corpo.is_empty() {
+                            return;
+                        }
+                        medida.citacoes += 1;
+                        let rodou = corpo
+                            .iter()
+                            .filter_map(|no| no.pos.as_ref())
+                            .any(|pos| self.vezes(pos) > 0);
+                        medida.citacoes_executadas += usize::from(rodou);
+                        return;
+                    }
+                    ASTItem::If => medida
+                        .ramos
+                        .push((pos.linha, self.ramos.get(&chave(pos)).copied())),
+                    _ => {}
+                }
+                let linha = medida.linhas.entry(pos.linha).or_default();
+                *linha = (*linha).max(self.vezes(pos));
+            });
+        }
+        medida
+    }
+
+    fn resumo(&self, arquivo: &str, medida: &Medida) {
+        let (linhas, cobertas) = (medida.linhas.len(), medida.linhas_cobertas());
+        eprintln!(
+            "{}: linhas {}/{} ({:.1}%), citacoes {}/{}, ramos {}/{}, funcoes {}/{}",
+            arquivo,
+            cobertas,
+            linhas,
+            porcento(cobertas, linhas),
+            medida.citacoes_executadas,
+            medida.citacoes,
+            medida.ramos_cobertos(),
+            medida.ramos.len() * 2,
+            medida.funcoes_cobertas(),
+            medida.funcoes.len()
+        );
+        let faltando: Vec<usize> = medida
+            .linhas
+            .iter()
+            .filter(|(_, vezes)| **vezes == 0)
+            .map(|(linha, _)| *linha)
+            .collect();
+        if !faltando.is_empty() {
+            eprintln!("    linhas que nao rodaram: {}", intervalos(&faltando));
+        }
+        for (linha, lados) in &medida.ramos {
+            match lados {
+                None => eprintln!("    if da linha {} nunca rodou", linha),
+                Some([0, _]) => {
+                    eprintln!("    if da linha {} nunca foi pro lado verdadeiro", linha)
+                }
+                Some([_, 0]) => eprintln!("    if da linha {} nunca foi pro lado falso", linha),
+                _ => {}
+            }
+        }
+    }
+
+    fn lcov(&self, arquivo: &str, medida: &Medida, saida: &mut String) {
+        let _ = writeln!(saida, "TN:\nSF:{}", arquivo);
+        for (nome, linha, _) in &medida.funcoes {
+            let _ = writeln!(saida, "FN:{},{}", linha, nome);
+        }
+        for (nome, _, vezes) in &medida.funcoes {
+            let _ = writeln!(saida, "FNDA:{},{}", vezes, nome);
+        }
+        let _ = writeln!(
+            saida,
+            "FNF:{}\nFNH:{}",
+            medida.funcoes.len(),
+            medida.funcoes_cobertas()
+        );
+        for (bloco, (linha, lados)) in medida.ramos.iter().enumerate() {
+            for lado in 0..2 {
+                let vezes = lados.map_or("-".to_owned(), |lados| lados[lado].to_string());
+                let _ = writeln!(saida, "BRDA:{},{},{},{}", linha, bloco, lado, vezes);
+            }
+        }
+        let _ = writeln!(
+            saida,
+            "BRF:{}\nBRH:{}",
+            medida.ramos.len() * 2,
+            medida.ramos_cobertos()
+        );
+        for (linha, vezes) in &medida.linhas {
+            let _ = writeln!(saida, "DA:{},{}", linha, vezes);
+        }
+        let _ = writeln!(
+            saida,
+            "LF:{}\nLH:{}\nend_of_record",
+            medida.linhas.len(),
+            medida.linhas_cobertas()
+        );
+    }
+
+    // fontes: (arquivo, conteudo) de cada arquivo carregado
+    pub fn relatorio(&self, fontes: &[(String, String)], caminho_lcov: &str) {
+        let mut lcov = String::new();
+        for (arquivo, conteudo) in fontes {
+            let medida = self.medir(arquivo, conteudo);
+            self.resumo(arquivo, &medida);
+            self.lcov(arquivo, &medida, &mut lcov);
+        }
+        match fs::write(caminho_lcov, lcov) {
+            Ok(()) => eprintln!("lcov em {}", caminho_lcov),
+            Err(erro) => eprintln!("nao deu pra escrever {}: {}", caminho_lcov, erro),
+        }
+    }
+}
@@ -0,0 +1,558 @@
+// ---------- Dap ----------
+
+// servidor do debug adapter protocol no stdin/stdout, pro vs code e o
+// neovim: paradas por linha, passo a passo, as chamadas de funcoes nomeadas
+// como quadros e a stack como variaveis. Tarefas do spawn viram threads.
+//
+// o launch recebe `program` e, opcional, `stopOnEntry` e `input` (as linhas
+// que o input vai ler, o stdin e do protocolo)
+//
+// o stdin e lido numa thread, assim pause, setBreakpoints e disconnect
+// chegam tambem com o programa rodando
+
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+use std::io;
+use std::rc::Rc;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+
+use crate::depurador::{Controle, Motivo, Parada, avaliar};
+use crate::json::{Json, escrever_mensagem, ler_mensagem};
+use crate::{
+    ASTItem, Entradas, ErroPSF, Nivel, No, Observador, PSFState, Pos, Saida, Stack, analisar,
+    cadeia_chamadas, interpretar_func, resolver, visitar_nos,
+};
+
+// de quantos em quantos passos olha se chegou pedido com o programa rodando
+const ENTRE_PEDIDOS: u64 = 256;
+
+// mensagens do stdin, Err pra mensagem invalida
+type Pedidos = Receiver<Result<Json, String>>;
+
+fn ler_pedidos() -> Pedidos {
+    let (enviar, receber) = mpsc::channel();
+    thread::spawn(move || {
+        let mut entrada = io::stdin().lock();
+        loop {
+            let mensagem = match ler_mensagem(&mut entrada) {
+                Ok(Some(pedido)) => Ok(pedido),
+                Ok(None) => return,
+                Err(erro) => Err(erro.to_string()),
+            };
+            if enviar.send(mensagem).is_err() {
+                return;
+            }
+        }
+    });
+    receber
+}
+
+#[derive(Debug)]
+struct Sessao {
+    pedidos: Pedidos,
+    seq: i64,
+    controle: Controle,
+    parar_no_comeco: bool,
+    // ja parou alguma vez, a primeira parada no comeco e "entry"
+    parou: bool,
+}
+
+impl Sessao {
+    // proximo pedido do editor, None quando ele fechou o stdin
+    fn receber(&self) -> Option<Json> {
+        loop {
+            match self.pedidos.recv() {
+                Ok(Ok(pedido)) => return Some(pedido),
+                Ok(Err(erro)) => eprintln!("dap: mensagem invalida: {}", erro),
+                Err(_) => return None,
+            }
+        }
+    }
+
+    fn enviar(&mut self, tipo: &str, mut pares: Vec<(&str, Json)>) {
+        self.seq += 1;
+        pares.insert(0, ("seq", Json::Numero(self.seq)));
+        pares.insert(1, ("type", tipo.into()));
+        // body e opcional, null confunde alguns editores
+        pares.retain(|(chave, valor)| *chave != "body" || *valor != Json::Null);
+        // stdout fechado e o editor que foi embora
+        if escrever_mensagem(&mut io::stdout().lock(), &Json::objeto(pares)).is_err() {
+            std::process::exit(0);
+        }
+    }
+
+    fn responder(&mut self, pedido: &Json, corpo: Json) {
+        self.enviar(
+            "response",
+            vec![
+                ("request_seq", pedido.get("seq").clone()),
+                ("success", true.into()),
+                ("command", pedido.get("command").clone()),
+                ("body", corpo),
+            ],
+        );
+    }
+
+    fn recusar(&mut self, pedido: &Json, mensagem: &str) {
+        self.enviar(
+            "response",
+            vec![
+                ("request_seq", pedido.get("seq").clone()),
+                ("success", false.into()),
+                ("command", pedido.get("command").clone()),
+                ("message", mensagem.into()),
+            ],
+        );
+    }
+
+    fn evento(&mut self, nome: &str, corpo: Json) {
+        self.enviar("event", vec![("event", nome.into()), ("body", corpo)]);
+    }
+
+    fn saida(&mut self, categoria: &str, texto: &str) {
+        self.evento(
+            "output",
+            Json::objeto([("category", categoria.into()), ("output", texto.into())]),
+        );
+    }
+
+    // o que o programa escreveu desde o ultimo passo
+    fn enviar_saida(&mut self, estado: &mut PSFState) {
+        if let Saida::Capturada(texto) = &mut estado.saida
+            && !texto.is_empty()
+        {
+            let texto = std::mem::take(texto);
+            self.saida("stdout", &texto);
+        }
+    }
+
+    // as paradas de um arquivo substituem as que ele tinha; a linha sem palavra
+    // vai pra proxima que tem
+    fn definir_paradas(&mut self, pedido: &Json) {
+        let argumentos = pedido.get("arguments");
+        let arquivo = argumentos.get("source").get("path").texto().unwrap_or("");
+        let executaveis = linhas_executaveis(arquivo);
+        self.controle.paradas.retain(|parada| match parada {
+            Parada::Linha(Some(local), _) => local != arquivo,
+            _ => true,
+        });
+        let mut paradas = vec![];
+        for pedida in argumentos.get("breakpoints").lista() {
+            let Some(linha) = pedida.get("line").numero() else {
+                continue;
+            };
+            let linha = executaveis.range(linha.max(0) as usize..).next().copied();
+            paradas.push(match linha {
+                Some(linha) => {
+                    let parada = Parada::Linha(Some(arquivo.to_owned()), linha);
+                    if !self.controle.paradas.contains(&parada) {
+                        self.controle.paradas.push(parada);
+                    }
+                    Json::objeto([("verified", true.into()), ("line", linha.into())])
+                }
+                None => Json::objeto([
+                    ("verified", false.into()),
+                    ("message", "nenhuma palavra nessa linha nem depois".into()),
+                ]),
+            });
+        }
+        self.responder(
+            pedido,
+            Json::objeto([("breakpoints", Json::Lista(paradas))]),
+        );
+    }
+
+    fn pausar(
+        &mut self,
+        estado: &mut PSFState,
+        no: &No,
+        stack_consumir: &Stack<No>,
+        motivo: &str,
+    ) -> Result<(), ErroPSF> {
+        self.parou = true;
+        self.evento(
+            "stopped",
+            Json::objeto([
+                ("reason", motivo.into()),
+                ("threadId", (estado.tarefa_atual + 1).into()),
+                ("allThreadsStopped", true.into()),
+            ]),
+        );
+        loop {
+            let Some(pedido) = self.receber() else {
+                std::process::exit(0);
+            };
+            match pedido.get("command").texto().unwrap_or("") {
+                "continue" => {
+                    self.controle.continuar();
+                    let corpo = Json::objeto([("allThreadsContinued", true.into())]);
+                    self.responder(&pedido, corpo);
+                    return Ok(());
+                }
+                "next" => {
+                    self.controle.proximo(estado, stack_consumir);
+                    self.responder(&pedido, Json::Null);
+                    return Ok(());
+                }
+                "stepIn" => {
+                    self.controle.entrar();
+                    self.responder(&pedido, Json::Null);
+                    return Ok(());
+                }
+                "stepOut" => {
+                    self.controle.sair(estado);
+                    self.responder(&pedido, Json::Null);
+                    return Ok(());
+                }
+                "threads" => self.responder(&pedido, threads(estado)),
+                "stackTrace" => {
+                    let thread = pedido.get("arguments").get("threadId").numero();
+                    let corpo = quadros(estado, no, stack_consumir, thread.unwrap_or(1) as usize);
+                    self.responder(&pedido, corpo);
+                }
+                "scopes" => {
+                    let quadro = pedido.get("arguments").get("frameId").numero();
+                    let thread = quadro.unwrap_or(0) >> 16;
+                    let escopo = Json::objeto([
+                        ("name", "Stack".into()),
+                        ("variablesReference", thread.into()),
+                        ("expensive", false.into()),
+                    ]);
+                    let corpo = Json::objeto([("scopes", Json::Lista(vec![escopo]))]);
+                    self.responder(&pedido, corpo);
+                }
+                "variables" => {
+                    let thread = pedido.get("arguments").get("variablesReference").numero();
+                    let corpo = variaveis(estado, thread.unwrap_or(0) as usize);
+                    self.responder(&pedido, corpo);
+                }
+                "evaluate" => {
+                    let expressao = pedido.get("arguments").get("expression").texto();
+                    let resultado = avaliar(estado, expressao.unwrap_or(""));
+                    self.enviar_saida(estado);
+                    let corpo = Json::objeto([
+                        ("result", resultado.into()),
+                        ("variablesReference", Json::Numero(0)),
+                    ]);
+                    self.responder(&pedido, corpo);
+                }
+                "setBreakpoints" => self.definir_paradas(&pedido),
+                "pause" => self.responder(&pedido, Json::Null),
+                "disconnect" | "terminate" => {
+                    self.responder(&pedido, Json::Null);
+                    std::process::exit(0);
+                }
+                _ => self.recusar(&pedido, "pedido nao suportado com o programa parado"),
+            }
+        }
+    }
+
+    // atende o que chegou com o programa rodando, true se pediu pra pausar
+    fn atender_rodando(&mut self, estado: &PSFState) -> bool {
+        let mut pausar = false;
+        loop {
+            let pedido = match self.pedidos.try_recv() {
+                Ok(Ok(pedido)) => pedido,
+                Ok(Err(erro)) => {
+                    eprintln!("dap: mensagem invalida: {}", erro);
+                    continue;
+                }
+                Err(TryRecvError::Empty) => return pausar,
+                Err(TryRecvError::Disconnected) => std::process::exit(0),
+            };
+            match pedido.get("command").texto().unwrap_or("") {
+                "pause" => {
+                    self.responder(&pedido, Json::Null);
+                    pausar = true;
+                }
+                "continue" => {
+                    let corpo = Json::objeto([("allThreadsContinued", true.into())]);
+                    self.responder(&pedido, corpo);
+                }
+                "setBreakpoints" => self.definir_paradas(&pedido),
+                "threads" => self.responder(&pedido, threads(estado)),
+                "disconnect" | "terminate" => {
+                    self.responder(&pedido, Json::Null);
+                    std::process::exit(0);
+                }
+                _ => self.recusar(&pedido, "pedido nao suportado com o programa rodando"),
+            }
+        }
+    }
+
+    fn observar(
+        &mut self,
+        estado: &mut PSFState,
+        no: &No,
+        stack_consumir: &Stack<No>,
+    ) -> Result<(), ErroPSF> {
+        self.enviar_saida(estado);
+        self.controle.acompanhar(stack_consumir);
+        if estado.passos.is_multiple_of(ENTRE_PEDIDOS) && self.atender_rodando(estado) {
+            return self.pausar(estado, no, stack_consumir, "pause");
+        }
+        let motivo = match self.controle.parar(estado, no, stack_consumir) {
+            Some(Motivo::Parada) => "breakpoint",
+            Some(Motivo::Passo) if !self.parou && self.parar_no_comeco => "entry",
+            Some(Motivo::Passo) => "step",
+            None => return Ok(()),
+        };
+        self.pausar(estado, no, stack_consumir, motivo)
+    }
+}
+
+impl Observador for Rc<RefCell<Sessao>> {
+    fn passo(
+        &mut self,
+        estado: &mut PSFState,
+        no: &No,
+        stack_consumir: &Stack<No>,
+    ) -> Result<(), ErroPSF> {
+        self.borrow_mut().observar(estado, no, stack_consumir)
+    }
+}
+
+// linhas com alguma palavra do codigo, vazio se o arquivo nao abre
+fn linhas_executaveis(arquivo: &str) -> BTreeSet<usize> {
+    let mut linhas = BTreeSet::new();
+    let Ok(conteudo) = std::fs::read_to_string(arquivo) else {
+        return linhas;
+    };
+    let (ast, _) = analisar(&conteudo, arquivo);
+    for definicao in &ast {
+        visitar_nos(&definicao.corpo, &mut |no| {
+            if let Some(pos) = &no.pos {
+                linhas.insert(pos.linha);
+            }
+        });
+    }
+    linhas
+}
+
+// a tarefa atual e as que estao esperando a vez, id da thread e o da tarefa + 1
+fn threads(estado: &PSFState) -> Json {
+    let ids = std::iter::once(estado.tarefa_atual).chain(estado.tarefas.iter().map(|t| t.id));
+    let threads = ids
+        .map(|id| {
+            let nome = match id {
+                0 => "main".to_owned(),
+                id => format!("tarefa {}", id),
+            };
+            Json::objeto([("id", (id + 1).into()), ("name", nome.into())])
+        })
+        .collect();
+    Json::objeto([("threads", Json::Lista(threads))])
+}
+
+fn pos_json(pos: Option<&Pos>) -> Vec<(&'static str, Json)> {
+    match pos {
+        Some(pos) => vec![
+            (
+                "source",
+                Json::objeto([("path", Json::from(&*pos.arquivo))]),
+            ),
+            ("line", pos.linha.into()),
+            ("column", pos.coluna.into()),
+        ],
+        None => vec![("line", Json::Numero(0)), ("column", Json::Numero(0))],
+    }
+}
+
+// um quadro por funcao nomeada em execucao, a mais interna primeiro; cada um
+// aponta pra onde esta executando, o de fora pra onde chamou o de dentro
+fn quadros(estado: &PSFState, no: &No, stack_consumir: &Stack<No>, thread: usize) -> Json {
+    let (consumir, atual) = if thread == estado.tarefa_atual + 1 {
+        (stack_consumir, no.pos.as_deref())
+    } else {
+        match estado.tarefas.iter().find(|t| t.id + 1 == thread) {
+            Some(tarefa) => (
+                &tarefa.stack_consumir,
+                tarefa
+                    .stack_consumir
+                    .lista
+                    .last()
+                    .and_then(|n| n.pos.as_deref()),
+            ),
+            None => return Json::objeto([("stackFrames", Json::Lista(vec![]))]),
+        }
+    };
+    let cadeia = cadeia_chamadas(consumir);
+    let mut lista = vec![];
+    for (i, quadro) in cadeia.iter().enumerate().rev() {
+        let pos = match cadeia.get(i + 1) {
+            Some(dentro) => dentro.pos.as_deref(),
+            None => atual,
+        };
+        let mut pares = vec![
+            ("id", ((thread << 16) | i).into()),
+            ("name", quadro.to_string().into()),
+        ];
+        pares.extend(pos_json(pos));
+        lista.push(Json::objeto(pares));
+    }
+    if lista.is_empty() {
+        let mut pares = vec![("id", (thread << 16).into()), ("name", "<topo>".into())];
+        pares.extend(pos_json(atual));
+        lista.push(Json::objeto(pares));
+    }
+    Json::objeto([
+        ("totalFrames", lista.len().into()),
+        ("stackFrames", Json::Lista(lista)),
+    ])
+}
+
+// a stack da thread, o topo primeiro, numerada a partir do fundo
+fn variaveis(estado: &PSFState, thread: usize) -> Json {
+    let stack = if thread == estado.tarefa_atual + 1 {
+        &estado.stack
+    } else {
+        match estado.tarefas.iter().find(|t| t.id + 1 == thread) {
+            Some(tarefa) => &tarefa.stack,
+            None => return Json::objeto([("variables", Json::Lista(vec![]))]),
+        }
+    };
+    let variaveis = stack
+        .lista
+        .iter()
+        .enumerate()
+        .rev()
+        .map(|(i, item)| {
+            let valor = match item {
+                crate::Item::String(s) => format!("{:?}", s),
+                outro => outro.to_string(),
+            };
+            Json::objeto([
+                ("name", format!("[{}]", i).into()),
+                ("value", valor.into()),
+                ("type", item.tipo().into()),
+                ("variablesReference", Json::Numero(0)),
+            ])
+        })
+        .collect();
+    Json::objeto([("variables", Json::Lista(variaveis))])
+}
+
+// roda a main do programa ja carregado em `estado` ate acabar
+fn rodar(sessao: &Rc<RefCell<Sessao>>, mut estado: PSFState) {
+    estado.saida = Saida::Capturada(String::new());
+    estado.observadores.push(Box::new(sessao.clone()));
+    estado.comecar_execucao();
+    let chamada = vec![No::sem_pos(ASTItem::FuncCallNamed("main".to_owned()))];
+    let resultado = interpretar_func(&mut estado, chamada);
+    estado.observadores.clear();
+
+    let mut sessao = sessao.borrow_mut();
+    sessao.enviar_saida(&mut estado);
+    let codigo = match resultado {
+        Ok(()) => 0,
+        Err(erro) => {
+            sessao.saida("stderr", &format!("erro: {}\n", erro.relatorio()));
+            1
+        }
+    };
+    sessao.evento("exited", Json::objeto([("exitCode", Json::Numero(codigo))]));
+    sessao.evento("terminated", Json::objeto([]));
+}
+
+// arquivo pronto pra rodar, ou os erros de sintaxe e de nome
+fn carregar(base: &PSFState, arquivo: &str, entradas: Vec<String>) -> Result<PSFState, String> {
+    let conteudo =
+        std::fs::read_to_string(arquivo).map_err(|erro| format!("{}: {}", arquivo, erro))?;
+    let (ast, mut diagnosticos) = analisar(&conteudo, arquivo);
+    if diagnosticos.is_empty() {
+        diagnosticos = resolver(&ast, &base.funcoes);
+    }
+    let erros: Vec<String> = diagnosticos
+        .iter()
+        .filter(|d| d.nivel == Nivel::Erro)
+        .map(|d| d.to_string())
+        .collect();
+    if !erros.is_empty() {
+        return Err(erros.join("\n"));
+    }
+    let mut estado = base.recomecar();
+    estado.load_ast(ast);
+    estado.entradas = Some(Entradas {
+        gravadas: entradas,
+        proxima: 0,
+        fechada: true,
+    });
+    Ok(estado)
+}
+
+pub fn servir(base: &PSFState) {
+    let sessao = Rc::new(RefCell::new(Sessao {
+        pedidos: ler_pedidos(),
+        seq: 0,
+        controle: Controle::new(vec![], false),
+        parar_no_comeco: false,
+        parou: false,
+    }));
+    let mut programa: Option<PSFState> = None;
+    loop {
+        let Some(pedido) = sessao.borrow().receber() else {
+            return;
+        };
+        let mut s = sessao.borrow_mut();
+        match pedido.get("command").texto().unwrap_or("") {
+            "initialize" => {
+                let capacidades = Json::objeto([
+                    ("supportsConfigurationDoneRequest", true.into()),
+                    ("supportsEvaluateForHovers", false.into()),
+                    ("supportsTerminateRequest", true.into()),
+                ]);
+                s.responder(&pedido, capacidades);
+                s.evento("initialized", Json::Null);
+            }
+            "launch" => {
+                let argumentos = pedido.get("arguments");
+                let Some(arquivo) = argumentos.get("program").texto() else {
+                    s.recusar(&pedido, "launch precisa de `program`");
+                    continue;
+                };
+                let entradas = argumentos
+                    .get("input")
+                    .lista()
+                    .iter()
+                    .filter_map(|linha| linha.texto().map(|l| format!("{}\n", l)))
+                    .collect();
+                match carregar(base, arquivo, entradas) {
+                    Ok(estado) => {
+                        programa = Some(estado);
+                        s.parar_no_comeco = argumentos.get("stopOnEntry").bool() == Some(true);
+                        s.responder(&pedido, Json::Null);
+                    }
+                    Err(erros) => {
+                        s.saida("stderr", &format!("{}\n", erros));
+                        s.recusar(&pedido, &erros);
+                    }
+                }
+            }
+            "setBreakpoints" => s.definir_paradas(&pedido),
+            "setExceptionBreakpoints" => s.responder(&pedido, Json::objeto([])),
+            "threads" => {
+                let thread = Json::objeto([("id", Json::Numero(1)), ("name", "main".into())]);
+                s.responder(
+                    &pedido,
+                    Json::objeto([("threads", Json::Lista(vec![thread]))]),
+                );
+            }
+            "configurationDone" => {
+                s.responder(&pedido, Json::Null);
+                if let Some(estado) = programa.take() {
+                    if s.parar_no_comeco {
+                        s.controle.entrar();
+                    }
+                    drop(s);
+                    rodar(&sessao, estado);
+                }
+            }
+            "disconnect" | "terminate" => {
+                s.responder(&pedido, Json::Null);
+                return;
+            }
+            _ => s.recusar(&pedido, "pedido nao suportado"),
+        }
+    }
+}
@@ -0,0 +1,713 @@
+// ---------- Depurador ----------
+
+// --debug: para antes de uma palavra e le comandos do stdin, as paradas sao
+// por funcao (antes da chamada), por linha ou pela palavra `break` no codigo
+//
+// cada passo guarda o que mudou na stack, e voltar no tempo e rodar de novo
+// do comeco ate o passo pedido, com as respostas do input gravadas e a saida
+// que ja foi mostrada escondida
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+use crate::rastreio::{interno, palavra};
+use crate::{
+    ASTItem, Entradas, ErroPSF, Item, No, Observador, PSFState, Pos, Saida, Stack, cadeia_chamadas,
+    empilhar, executar_ate_yield, formatar_itens, interpretar_func, mesmo_item,
+    tokenizar_e_gerar_ast,
+};
+
+// itens da continuacao mostrados pelo comando `k`
+const ITENS_CONTINUACAO: usize = 20;
+// passos guardados no historico, os mais antigos saem
+const HISTORICO_MAXIMO: usize = 100_000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Parada {
+    Funcao(String),
+    // sem arquivo vale a linha de qualquer um
+    Linha(Option<String>, usize),
+}
+
+impl Parada {
+    // `12`, `arquivo.psf:12` ou nome de funcao
+    pub fn ler(texto: &str) -> Parada {
+        if let Ok(n) = texto.parse() {
+            return Parada::Linha(None, n);
+        }
+        match texto.rsplit_once(':') {
+            Some((arquivo, linha)) if let Ok(n) = linha.parse() => {
+                Parada::Linha(Some(arquivo.to_owned()), n)
+            }
+            _ => Parada::Funcao(texto.to_owned()),
+        }
+    }
+}
+
+impl fmt::Display for Parada {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Parada::Funcao(nome) => write!(f, "funcao {}", nome),
+            Parada::Linha(None, linha) => write!(f, "linha {}", linha),
+            Parada::Linha(Some(arquivo), linha) => write!(f, "linha {} de {}", linha, arquivo),
+        }
+    }
+}
+
+// o caminho pode vir relativo de um lado e absoluto do outro
+pub fn mesmo_arquivo(a: &str, b: &str) -> bool {
+    let (curto, longo) = if a.len() <= b.len() { (a, b) } else { (b, a) };
+    let curto = curto.trim_start_matches("./");
+    longo == curto || longo.ends_with(&format!("/{}", curto))
+}
+
+// quando parar de novo, fora as paradas; tam e da stack de consumo
+#[derive(Debug)]
+enum Modo {
+    Continuar,
+    // qualquer proxima palavra, entrando em chamadas e citacoes
+    Passo,
+    // a proxima palavra do mesmo corpo, sem entrar
+    Proximo { tarefa: usize, tam: usize },
+    // a primeira palavra depois que o corpo atual acabar
+    Sair { tarefa: usize, tam: usize },
+}
+
+// onde parar: paradas, passo a passo e os corpos em execucao pra saber o
+// que e entrar e sair; usado pelo --debug e pelo servidor dap
+#[derive(Debug)]
+pub struct Controle {
+    pub paradas: Vec<Parada>,
+    modo: Modo,
+    // tamanho da stack de consumo quando cada corpo em execucao comecou
+    corpos: Vec<usize>,
+    tam_anterior: Option<usize>,
+    linha_anterior: Option<usize>,
+}
+
+// por que parou
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Motivo {
+    Passo,
+    Parada,
+}
+
+impl Controle {
+    pub fn new(paradas: Vec<Parada>, parar_no_comeco: bool) -> Controle {
+        Controle {
+            paradas,
+            modo: if parar_no_comeco {
+                Modo::Passo
+            } else {
+                Modo::Continuar
+            },
+            corpos: vec![],
+            tam_anterior: None,
+            linha_anterior: None,
+        }
+    }
+
+    // antes de rodar de novo do comeco
+    pub fn reiniciar(&mut self) {
+        self.corpos.clear();
+        self.tam_anterior = None;
+        self.linha_anterior = None;
+    }
+
+    // chamado em todo passo, ate nos internos
+    pub fn acompanhar(&mut self, stack_consumir: &Stack<No>) {
+        let tam = stack_consumir.len();
+        // a palavra anterior empilhou um corpo: chamada, !, if...
+        if let Some(anterior) = self.tam_anterior
+            && tam >= anterior
+        {
+            self.corpos.push(anterior);
+        }
+        while self.corpos.last().is_some_and(|&corpo| tam < corpo) {
+            self.corpos.pop();
+        }
+        self.tam_anterior = Some(tam);
+    }
+
+    pub fn parar(
+        &mut self,
+        estado: &PSFState,
+        no: &No,
+        stack_consumir: &Stack<No>,
+    ) -> Option<Motivo> {
+        if interno(&no.item) {
+            return None;
+        }
+        let linha = no.pos.as_ref().map(|p| p.linha);
+        let arquivo = no.pos.as_ref().map(|p| &*p.arquivo);
+        let nova_linha = linha != self.linha_anterior;
+        self.linha_anterior = linha;
+        let parada = matches!(no.item, ASTItem::Break)
+            || self.paradas.iter().any(|parada| match parada {
+                Parada::Funcao(nome) => {
+                    matches!(&no.item, ASTItem::FuncCallNamed(chamada) if chamada == nome)
+                }
+                Parada::Linha(local, n) => {
+                    nova_linha
+                        && linha == Some(*n)
+                        && local
+                            .as_deref()
+                            .is_none_or(|local| arquivo.is_some_and(|a| mesmo_arquivo(local, a)))
+                }
+            });
+        if parada {
+            return Some(Motivo::Parada);
+        }
+        let tam = stack_consumir.len();
+        // passo a passo so para no que esta escrito no codigo
+        let passo = match self.modo {
+            _ if no.pos.is_none() => false,
+            Modo::Continuar => false,
+            Modo::Passo => true,
+            Modo::Proximo {
+                tarefa,
+                tam: limite,
+            }
+            | Modo::Sair {
+                tarefa,
+                tam: limite,
+            } => tarefa == estado.tarefa_atual && tam < limite,
+        };
+        passo.then_some(Motivo::Passo)
+    }
+
+    pub fn continuar(&mut self) {
+        self.modo = Modo::Continuar;
+    }
+
+    pub fn entrar(&mut self) {
+        self.modo = Modo::Passo;
+    }
+
+    pub fn proximo(&mut self, estado: &PSFState, stack_consumir: &Stack<No>) {
+        self.modo = Modo::Proximo {
+            tarefa: estado.tarefa_atual,
+            tam: stack_consumir.len(),
+        };
+    }
+
+    pub fn sair(&mut self, estado: &PSFState) {
+        self.modo = match self.corpos.last() {
+            Some(&tam) => Modo::Sair {
+                tarefa: estado.tarefa_atual,
+                tam,
+            },
+            None => Modo::Continuar,
+        };
+    }
+}
+
+// o que um passo fez na stack: tirou `removidos` e colocou `novos` acima
+// dos `mantidos` primeiros itens
+#[derive(Debug)]
+struct Registro {
+    passo: u64,
+    palavra: String,
+    pos: Option<Pos>,
+    // se o passo a passo para nele
+    parada: bool,
+    mantidos: usize,
+    removidos: Vec<Item>,
+    novos: Vec<Item>,
+}
+
+impl Registro {
+    fn mudou(&self, posicao: usize) -> bool {
+        posicao >= self.mantidos
+            && posicao < self.mantidos + self.removidos.len().max(self.novos.len())
+    }
+}
+
+impl fmt::Display for Registro {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:>6}  {:<12}  [{}] -> [{}]",
+            self.passo,
+            self.palavra,
+            formatar_itens(&self.removidos),
+            formatar_itens(&self.novos)
+        )?;
+        if let Some(pos) = &self.pos {
+            write!(f, "  {}", pos)?;
+        }
+        Ok(())
+    }
+}
+
+// passo que ainda nao terminou, a stack de antes pra comparar com a de depois
+#[derive(Debug)]
+struct Pendente {
+    registro: Registro,
+    stack: Vec<Item>,
+}
+
+#[derive(Debug)]
+pub struct Depurador {
+    controle: Controle,
+    arquivo: String,
+    fonte: Vec<String>,
+    ultimo_comando: String,
+    historico: VecDeque<Registro>,
+    pendente: Option<Pendente>,
+    // ultimo passo que ja entrou no historico
+    gravado_ate: u64,
+    passo_atual: u64,
+    // rodando de novo ate esse passo, sem parar e sem mostrar a saida
+    alvo: Option<u64>,
+    entradas: Entradas,
+}
+
+impl Depurador {
+    // sem paradas comeca parado na primeira palavra
+    pub fn new(arquivo: &str, entrada: &str, paradas: Vec<Parada>) -> Depurador {
+        let parar_no_comeco = paradas.is_empty();
+        Depurador {
+            controle: Controle::new(paradas, parar_no_comeco),
+            arquivo: arquivo.to_owned(),
+            fonte: entrada.lines().map(str::to_owned).collect(),
+            ultimo_comando: String::new(),
+            historico: VecDeque::new(),
+            pendente: None,
+            gravado_ate: 0,
+            passo_atual: 0,
+            alvo: None,
+            entradas: Entradas::default(),
+        }
+    }
+
+    // antes de cada execucao, a primeira ou uma repeticao
+    fn comecar(&mut self, estado: &mut PSFState) {
+        self.controle.reiniciar();
+        self.pendente = None;
+        let mut entradas = std::mem::take(&mut self.entradas);
+        entradas.proxima = 0;
+        estado.entradas = Some(entradas);
+        if self.alvo.is_some() {
+            estado.saida = Saida::Capturada(String::new());
+        }
+    }
+
+    fn terminar(&mut self, estado: &mut PSFState) {
+        self.registrar(&estado.stack.lista);
+        self.passo_atual = estado.passos + 1;
+        self.alvo = None;
+        estado.saida = Saida::Terminal;
+    }
+
+    // fecha o passo pendente com a stack de depois dele
+    fn registrar(&mut self, depois: &[Item]) {
+        let Some(Pendente {
+            mut registro,
+            stack: antes,
+        }) = self.pendente.take()
+        else {
+            return;
+        };
+        let mantidos = antes
+            .iter()
+            .zip(depois)
+            .take_while(|(a, b)| mesmo_item(a, b))
+            .count();
+        registro.mantidos = mantidos;
+        registro.removidos = antes[mantidos..].to_vec();
+        registro.novos = depois[mantidos..].to_vec();
+        if self.historico.len() == HISTORICO_MAXIMO {
+            self.historico.pop_front();
+        }
+        self.historico.push_back(registro);
+    }
+
+    fn mostrar_local(&self, no: &No) {
+        let Some(pos) = &no.pos else {
+            println!(
+                "passo {}  <sem posicao>: {}",
+                self.passo_atual,
+                palavra(&no.item)
+            );
+            return;
+        };
+        println!("passo {}  {}: {}", self.passo_atual, pos, palavra(&no.item));
+        if *pos.arquivo == *self.arquivo
+            && let Some(linha) = self.fonte.get(pos.linha - 1)
+        {
+            println!("{:>4} | {}", pos.linha, linha);
+        }
+    }
+
+    // passo pedido por r, w ou g; None e comando errado ou passo que nao tem
+    fn destino(&self, comando: &str, arg: &str) -> Option<u64> {
+        let anteriores = || {
+            self.historico
+                .iter()
+                .rev()
+                .filter(|r| r.passo < self.passo_atual)
+        };
+        match comando {
+            "r" | "back" => {
+                let n = match arg {
+                    "" => 1,
+                    n => n.parse().ok().filter(|&n| n > 0)?,
+                };
+                let registro = anteriores().filter(|r| r.parada).nth(n - 1);
+                if registro.is_none() {
+                    println!("o historico nao volta tanto");
+                }
+                registro.map(|r| r.passo)
+            }
+            "w" | "changed" => {
+                let Ok(posicao) = arg.parse() else {
+                    println!("w precisa da posicao na stack, 0 e o fundo");
+                    return None;
+                };
+                let registro = anteriores().find(|r| r.mudou(posicao));
+                match registro {
+                    Some(r) => println!("posicao {} mudou no passo {}", posicao, r.passo),
+                    None => println!("posicao {} nao mudou no historico", posicao),
+                }
+                registro.map(|r| r.passo)
+            }
+            "g" | "goto" => {
+                let Ok(passo) = arg.parse() else {
+                    println!("g precisa do numero do passo");
+                    return None;
+                };
+                Some(passo)
+            }
+            _ => None,
+        }
+    }
+
+    // Err interrompe a execucao pra rodar de novo ate o alvo
+    fn ir(&mut self, passo: u64) -> Result<(), ErroPSF> {
+        self.alvo = Some(passo);
+        if passo < self.passo_atual {
+            return Err(ErroPSF::new(
+                "interrompido",
+                format!("voltando pro passo {}", passo),
+            ));
+        }
+        Ok(())
+    }
+
+    fn mostrar_historico(&self, arg: &str) {
+        let n = arg.parse().unwrap_or(10);
+        let mut registros: Vec<&Registro> = self
+            .historico
+            .iter()
+            .rev()
+            .filter(|r| r.passo < self.passo_atual)
+            .take(n)
+            .collect();
+        registros.reverse();
+        for registro in registros {
+            println!("{}", registro);
+        }
+    }
+
+    // le comandos ate um que continue a execucao
+    fn pausar(
+        &mut self,
+        estado: &mut PSFState,
+        no: &No,
+        stack_consumir: &Stack<No>,
+    ) -> Result<(), ErroPSF> {
+        self.mostrar_local(no);
+        loop {
+            let Some(linha) = self.ler_comando() else {
+                // stdin acabou, roda ate o fim sem parar mais
+                self.controle.paradas.clear();
+                self.controle.continuar();
+                return Ok(());
+            };
+            let (comando, arg) = linha.split_once(' ').unwrap_or((&linha, ""));
+            let arg = arg.trim();
+            match comando {
+                "c" | "continue" => {
+                    self.controle.continuar();
+                    return Ok(());
+                }
+                "s" | "step" => {
+                    self.controle.entrar();
+                    return Ok(());
+                }
+                "n" | "next" => {
+                    self.controle.proximo(estado, stack_consumir);
+                    return Ok(());
+                }
+                "o" | "out" => {
+                    self.controle.sair(estado);
+                    return Ok(());
+                }
+                "r" | "back" | "w" | "changed" | "g" | "goto" => match self.destino(comando, arg) {
+                    Some(passo) if passo != self.passo_atual => return self.ir(passo),
+                    _ => {}
+                },
+                "hist" => self.mostrar_historico(arg),
+                "b" | "break" if !arg.is_empty() => {
+                    let parada = Parada::ler(arg);
+                    println!("parada na {}", parada);
+                    self.controle.paradas.push(parada);
+                }
+                "d" | "delete" if !arg.is_empty() => {
+                    let parada = Parada::ler(arg);
+                    match self.controle.paradas.iter().position(|p| *p == parada) {
+                        Some(i) => {
+                            self.controle.paradas.remove(i);
+                        }
+                        None => println!("nao tem parada na {}", parada),
+                    }
+                }
+                "l" | "list" => {
+                    if self.controle.paradas.is_empty() {
+                        println!("nenhuma parada");
+                    }
+                    for parada in &self.controle.paradas {
+                        println!("{}", parada);
+                    }
+                }
+                "p" | "stack" => println!("{}", texto_stack(estado)),
+                "k" | "cont" => {
+                    let lista = &stack_consumir.lista;
+                    for no in lista.iter().rev().take(ITENS_CONTINUACAO) {
+                        let pos = no
+                            .pos
+                            .as_ref()
+                            .map_or(String::new(), |p| format!("  {}", p));
+                        println!("    {}{}", palavra(&no.item), pos);
+                    }
+                    if lista.len() > ITENS_CONTINUACAO {
+                        println!("    ... mais {}", lista.len() - ITENS_CONTINUACAO);
+                    }
+                }
+                "bt" => {
+                    for (i, quadro) in cadeia_chamadas(stack_consumir).iter().rev().enumerate() {
+                        match &quadro.pos {
+                            Some(pos) => println!("#{} {} chamada em {}", i, quadro, pos),
+                            None => println!("#{} {}", i, quadro),
+                        }
+                    }
+                }
+                "e" | "eval" if !arg.is_empty() => println!("{}", avaliar(estado, arg)),
+                "q" | "quit" => std::process::exit(0),
+                "h" | "help" => print_ajuda(),
+                _ => println!("comando desconhecido, h mostra a ajuda"),
+            }
+        }
+    }
+
+    // depois que o programa acabou so da pra voltar no tempo ou sair;
+    // devolve se e pra rodar de novo
+    fn fim(&mut self, estado: &PSFState) -> bool {
+        println!("a execucao acabou no passo {}", self.passo_atual - 1);
+        loop {
+            let Some(linha) = self.ler_comando() else {
+                return false;
+            };
+            let (comando, arg) = linha.split_once(' ').unwrap_or((&linha, ""));
+            let arg = arg.trim();
+            match comando {
+                "r" | "back" | "w" | "changed" | "g" | "goto" => match self.destino(comando, arg) {
+                    Some(passo) if passo < self.passo_atual => {
+                        self.alvo = Some(passo);
+                        return true;
+                    }
+                    Some(_) => println!("a execucao nao passou desse passo"),
+                    None => {}
+                },
+                "hist" => self.mostrar_historico(arg),
+                "p" | "stack" => println!("{}", texto_stack(estado)),
+                "c" | "continue" | "q" | "quit" => return false,
+                "h" | "help" => print_ajuda(),
+                _ => println!("a execucao acabou, so r, w, g, hist, p e q funcionam"),
+            }
+        }
+    }
+
+    // linha vazia repete o ultimo comando, None quando o stdin acaba
+    fn ler_comando(&mut self) -> Option<String> {
+        print!("(debug) ");
+        _ = io::stdout().flush();
+        let mut linha = String::new();
+        if io::stdin().read_line(&mut linha).unwrap_or(0) == 0 {
+            println!();
+            return None;
+        }
+        let linha = match linha.trim() {
+            "" => self.ultimo_comando.clone(),
+            comando => comando.to_owned(),
+        };
+        self.ultimo_comando = linha.clone();
+        Some(linha)
+    }
+
+    fn observar(
+        &mut self,
+        estado: &mut PSFState,
+        no: &No,
+        stack_consumir: &Stack<No>,
+    ) -> Result<(), ErroPSF> {
+        self.passo_atual = estado.passos;
+        self.registrar(&estado.stack.lista);
+        // repeticao nao grava de novo o que ja esta no historico
+        if estado.passos > self.gravado_ate {
+            self.gravado_ate = estado.passos;
+            self.pendente = Some(Pendente {
+                registro: Registro {
+                    passo: estado.passos,
+                    palavra: palavra(&no.item),
+                    pos: no.pos.as_deref().cloned(),
+                    parada: no.pos.is_some() && !interno(&no.item),
+                    mantidos: 0,
+                    removidos: vec![],
+                    novos: vec![],
+                },
+                stack: estado.stack.lista.clone(),
+            });
+        }
+
+        self.controle.acompanhar(stack_consumir);
+
+        if let Some(alvo) = self.alvo {
+            if estado.passos < alvo {
+                return Ok(());
+            }
+            self.alvo = None;
+            estado.saida = Saida::Terminal;
+            return self.pausar(estado, no, stack_consumir);
+        }
+        if self.controle.parar(estado, no, stack_consumir).is_some() {
+            return self.pausar(estado, no, stack_consumir);
+        }
+        Ok(())
+    }
+}
+
+// o mesmo depurador acompanha todas as vezes que o programa roda
+impl Observador for Rc<RefCell<Depurador>> {
+    fn passo(
+        &mut self,
+        estado: &mut PSFState,
+        no: &No,
+        stack_consumir: &Stack<No>,
+    ) -> Result<(), ErroPSF> {
+        self.borrow_mut().observar(estado, no, stack_consumir)
+    }
+}
+
+pub fn texto_stack(estado: &PSFState) -> String {
+    format!(
+        "stack ({}): {}",
+        estado.stack.len(),
+        formatar_itens(&estado.stack.lista)
+    )
+}
+
+// roda a expressao na stack de verdade, o que ela mudar fica; devolve a
+// stack como ficou ou o erro
+pub fn avaliar(estado: &mut PSFState, expressao: &str) -> String {
+    let funcao = match tokenizar_e_gerar_ast(expressao, "<debug>", true) {
+        Ok(ast) => ast.first().unwrap().corpo.to_vec(),
+        Err(erro) => return erro.to_string(),
+    };
+    // os passos do eval nao contam, senao voltar no tempo cai em outro lugar
+    let passos = estado.passos;
+    // nem o que ele ler com input, senao a repeticao le essas respostas no
+    // lugar das do programa
+    let entradas = estado.entradas.take();
+    estado.entradas = entradas.as_ref().map(|entradas| Entradas {
+        fechada: entradas.fechada,
+        ..Entradas::default()
+    });
+    let mut stack_consumir = Stack::new();
+    empilhar(&mut stack_consumir, &funcao);
+    let resultado = match executar_ate_yield(estado, &mut stack_consumir) {
+        Ok(_) => texto_stack(estado),
+        Err(erro) => erro.relatorio(),
+    };
+    estado.passos = passos;
+    estado.entradas = entradas;
+    resultado
+}
+
+fn print_ajuda() {
+    println!(
+        "comandos (linha vazia repete o ultimo)
+    c continue      roda ate a proxima parada
+    s step          proxima palavra, entrando em chamadas e citacoes
+    n next          proxima palavra sem entrar
+    o out           roda ate sair da funcao ou citacao atual
+    r back [N]      volta N palavras (padrao 1)
+    w changed I     volta pro ultimo passo que mudou a posicao I da stack, 0 e o fundo
+    g goto N        vai pro passo N, pra frente ou pra tras
+    hist [N]        mostra o que os ultimos N passos fizeram na stack
+    b break LOCAL   para na linha N, em ARQUIVO:N ou antes de chamar a funcao
+    d delete LOCAL  tira a parada
+    l list          lista as paradas
+    p stack         mostra a stack
+    k cont          mostra o que falta executar, o topo primeiro
+    bt              mostra as chamadas de funcoes nomeadas
+    e eval EXPR     roda EXPR na stack atual, voltar no tempo desfaz
+    q quit          sai"
+    );
+}
+
+// roda a main com o depurador, de novo do comeco cada vez que ele volta no tempo
+pub fn depurar(estado: &PSFState, depurador: Depurador) {
+    let depurador = Rc::new(RefCell::new(depurador));
+    let chamada = vec![No::sem_pos(ASTItem::FuncCallNamed("main".to_owned()))];
+    loop {
+        let mut execucao = estado.recomecar();
+        depurador.borrow_mut().comecar(&mut execucao);
+        execucao.observadores.push(Box::new(depurador.clone()));
+        execucao.comecar_execucao();
+        let resultado = interpretar_func(&mut execucao, chamada.clone());
+        execucao.observadores.clear();
+
+        let mut depurador = depurador.borrow_mut();
+        depurador.entradas = execucao.entradas.take().unwrap_or_default();
+        match resultado {
+            Err(erro) if erro.tipo == "interrompido" => continue,
+            Err(erro) => println!("\x1b[1;31merro\x1b[0m: {}", erro.relatorio()),
+            Ok(()) => {}
+        }
+        depurador.terminar(&mut execucao);
+        if !depurador.fim(&execucao) {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod testes {
+    use super::*;
+
+    #[test]
+    fn ler_parada() {
+        assert_eq!(Parada::ler("12"), Parada::Linha(None, 12));
+        assert_eq!(
+            Parada::ler("exemplos/06.psf:5"),
+            Parada::Linha(Some("exemplos/06.psf".to_owned()), 5)
+        );
+        assert_eq!(Parada::ler("desc"), Parada::Funcao("desc".to_owned()));
+    }
+
+    #[test]
+    fn caminho_relativo_e_absoluto() {
+        assert!(mesmo_arquivo(
+            "exemplos/06.psf",
+            "/root/crate/exemplos/06.psf"
+        ));
+        assert!(mesmo_arquivo("./06.psf", "exemplos/06.psf"));
+        assert!(!mesmo_arquivo("6.psf", "exemplos/06.psf"));
+        assert!(!mesmo_arquivo("exemplos/05.psf", "exemplos/06.psf"));
+    }
+}
@@ -0,0 +1,148 @@
+// ---------- Explicacao ----------
+
+// --explain: roda o programa e mostra a tabela do fim do exemplos/08, a stack
+// antes de cada palavra e o que ela escreveu
+//
+// 5 6 7 6 5 1 1     swapn
+// 5 6 7 6 1 5       print    -> 5
+
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+use crate::rastreio::{interno, palavra};
+use crate::{
+    ASTItem, ErroPSF, Func, Item, No, Observador, PSFState, Saida, Stack, cadeia_chamadas,
+    formatar_itens, interpretar_func, profundidade,
+};
+
+#[derive(Debug)]
+struct Linha {
+    stack: String,
+    palavra: String,
+    saida: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+struct Tabela {
+    linhas: Vec<Linha>,
+}
+
+impl Tabela {
+    // o que foi escrito desde a ultima linha e dela
+    fn recolher(&mut self, saida: &mut Saida) {
+        let Saida::Capturada(texto) = saida else {
+            return;
+        };
+        if texto.is_empty() {
+            return;
+        }
+        match self.linhas.last_mut() {
+            Some(linha) => linha
+                .saida
+                .extend(texto.lines().map(|l| format!("-> {}", l))),
+            None => print!("{}", texto),
+        }
+        texto.clear();
+    }
+
+    fn mostrar(&self) {
+        let largura_stack = self.linhas.iter().map(|l| l.stack.chars().count()).max();
+        let largura_palavra = self.linhas.iter().map(|l| l.palavra.chars().count()).max();
+        let (largura_stack, largura_palavra) = (
+            largura_stack.unwrap_or(0) + 2,
+            largura_palavra.unwrap_or(0) + 2,
+        );
+        for linha in &self.linhas {
+            let texto = format!(
+                "{:<largura_stack$}{:<largura_palavra$}{}",
+                linha.stack,
+                linha.palavra,
+                linha.saida.join(" ")
+            );
+            println!("{}", texto.trim_end());
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Explicacao {
+    // profundidade do codigo explicado, 1 no corpo da main
+    base: usize,
+    // chamadas de funcoes nomeadas viram uma linha so
+    colapsar: bool,
+    tabela: Rc<RefCell<Tabela>>,
+}
+
+impl Observador for Explicacao {
+    fn passo(
+        &mut self,
+        estado: &mut PSFState,
+        no: &No,
+        stack_consumir: &Stack<No>,
+    ) -> Result<(), ErroPSF> {
+        let mut tabela = self.tabela.borrow_mut();
+        tabela.recolher(&mut estado.saida);
+        // a tabela so sai no fim, mas o input le do terminal agora: o prompt
+        // aparece antes, e fica tambem na linha do input
+        if let ASTItem::Input = no.item
+            && let Some(Item::String(prompt)) = estado.stack.lista.last()
+        {
+            print!("{}", prompt);
+            _ = io::stdout().flush();
+        }
+        let profundidade = profundidade(&cadeia_chamadas(stack_consumir));
+        if interno(&no.item)
+            || profundidade < self.base
+            || (self.colapsar && profundidade > self.base)
+        {
+            return Ok(());
+        }
+        tabela.linhas.push(Linha {
+            stack: formatar_itens(&estado.stack.lista),
+            palavra: palavra(&no.item),
+            saida: vec![],
+        });
+        Ok(())
+    }
+}
+
+fn explicar(
+    estado: &mut PSFState,
+    funcao: Func,
+    base: usize,
+    colapsar: bool,
+) -> Result<(), ErroPSF> {
+    let tabela = Rc::new(RefCell::new(Tabela::default()));
+    let saida = std::mem::replace(&mut estado.saida, Saida::Capturada(String::new()));
+    estado.observadores.push(Box::new(Explicacao {
+        base,
+        colapsar,
+        tabela: tabela.clone(),
+    }));
+    estado.comecar_execucao();
+    let resultado = interpretar_func(estado, funcao);
+    estado.observadores.pop();
+
+    let mut tabela = tabela.borrow_mut();
+    tabela.recolher(&mut estado.saida);
+    estado.saida = saida;
+    // a stack como ficou no fim, sem palavra
+    tabela.linhas.push(Linha {
+        stack: formatar_itens(&estado.stack.lista),
+        palavra: String::new(),
+        saida: vec![],
+    });
+    tabela.mostrar();
+    resultado
+}
+
+pub fn explicar_main(estado: &mut PSFState, colapsar: bool) -> Result<(), ErroPSF> {
+    let chamada = vec![No::sem_pos(ASTItem::FuncCallNamed("main".to_owned()))];
+    explicar(estado, chamada, 1, colapsar)
+}
+
+// expressao solta, do repl
+pub fn explicar_expressao(estado: &mut PSFState, funcao: Func) -> Result<(), ErroPSF> {
+    explicar(estado, funcao, 0, false)
+}
@@ -0,0 +1,194 @@
+// ---------- Formatador ----------
+
+// reescreve o arquivo com indentacao de 4 espacos, mantendo as quebras de
+// linha, os comentarios e uma linha em branco onde tinha uma ou mais
+
+use std::collections::HashSet;
+
+use crate::{Diagnostico, Pos, Token, analisar, tokenizar_com_comentarios};
+
+const INDENTACAO: &str = "    ";
+
+pub fn texto(token: &Token) -> String {
+    match token {
+        Token::Igual => "=".to_owned(),
+        Token::ParenAbr => "(".to_owned(),
+        Token::ParenFec => ")".to_owned(),
+        Token::Interrogacao => "?".to_owned(),
+        Token::Exclamacao => "!".to_owned(),
+        Token::Mais => "+".to_owned(),
+        Token::Menor => "<".to_owned(),
+        Token::Maior => ">".to_owned(),
+        Token::Separador => "--".to_owned(),
+        Token::DoisPontos => ":".to_owned(),
+        Token::Numero(n) => n.to_string(),
+        Token::Simbolo(s) => s.clone(),
+        Token::String(s) => format!("\"{}\"", s),
+        Token::Comentario(s) => format!("#{}", s.trim_end()),
+    }
+}
+
+// string pode ter quebra de linha dentro
+fn linha_final(token: &Token, pos: &Pos) -> usize {
+    match token {
+        Token::String(s) => pos.linha + s.matches('\n').count(),
+        _ => pos.linha,
+    }
+}
+
+// indice do `?` de cada citacao que e um dos ramos de um if
+fn ramos_de_if(tokens: &[(Token, Pos)]) -> HashSet<usize> {
+    // elementos de cada sequencia aberta: onde comeca e se e citacao
+    let mut sequencias: Vec<Vec<(usize, bool)>> = vec![vec![]];
+    // onde comeca o elemento de cada parenteses aberto
+    let mut inicios = vec![];
+    let mut ramos = HashSet::new();
+    for (i, (token, _)) in tokens.iter().enumerate() {
+        match token {
+            // o `?` entra junto com o parenteses
+            Token::Comentario(_) | Token::Interrogacao => {}
+            Token::ParenAbr => {
+                let citacao = i > 0 && matches!(tokens[i - 1].0, Token::Interrogacao);
+                inicios.push(if citacao { i - 1 } else { i });
+                sequencias.push(vec![]);
+            }
+            Token::ParenFec => {
+                sequencias.pop();
+                if let Some(inicio) = inicios.pop() {
+                    let citacao = matches!(tokens[inicio].0, Token::Interrogacao);
+                    sequencias.last_mut().unwrap().push((inicio, citacao));
+                }
+            }
+            Token::Simbolo(nome) if nome == "if" => {
+                let sequencia = sequencias.last_mut().unwrap();
+                if let [.., (verdadeiro, true), (falso, true)] = sequencia.as_slice() {
+                    ramos.insert(*verdadeiro);
+                    ramos.insert(*falso);
+                }
+                sequencia.push((i, false));
+            }
+            _ => sequencias.last_mut().unwrap().push((i, false)),
+        }
+    }
+    ramos
+}
+
+// Err com os erros de sintaxe, arquivo que nao compila nao e formatado
+pub fn formatar(entrada: &str, arquivo: &str) -> Result<String, Vec<Diagnostico>> {
+    let (_, erros) = analisar(entrada, arquivo);
+    if !erros.is_empty() {
+        return Err(erros);
+    }
+    let (tokens, _) = tokenizar_com_comentarios(entrada, arquivo);
+    let ramos = ramos_de_if(&tokens);
+
+    let mut saida = String::new();
+    // indentacao da linha onde cada parenteses aberto comecou, e se e citacao
+    let mut abertos: Vec<(usize, bool)> = vec![];
+    let mut indentacao = 0;
+    let mut fim_anterior: Option<usize> = None;
+    let mut fechou_definicao = false;
+    for (i, (token, pos)) in tokens.iter().enumerate() {
+        let nova_linha = fim_anterior.is_none_or(|fim| pos.linha > fim);
+        if nova_linha {
+            if let Some(fim) = fim_anterior {
+                saida.push('\n');
+                // no maximo uma linha em branco, e sempre uma entre definicoes
+                if pos.linha > fim + 1 || fechou_definicao {
+                    saida.push('\n');
+                }
+            }
+            let conteudo = abertos.last().map_or(0, |(nivel, _)| nivel + 1);
+            indentacao = match token {
+                Token::ParenFec => abertos.last().map_or(0, |(nivel, _)| *nivel),
+                Token::Interrogacao if ramos.contains(&i) => conteudo + 1,
+                _ => conteudo,
+            };
+            saida.push_str(&INDENTACAO.repeat(indentacao));
+        } else {
+            let anterior = &tokens[i - 1].0;
+            let em_citacao = abertos.last().is_some_and(|(_, citacao)| *citacao);
+            let junto = matches!(anterior, Token::Interrogacao | Token::DoisPontos)
+                || matches!(token, Token::DoisPontos)
+                || (matches!(anterior, Token::ParenAbr) && em_citacao)
+                || (matches!(token, Token::ParenFec) && em_citacao);
+            if !junto {
+                saida.push(' ');
+            }
+        }
+
+        // comentario no fim da linha da definicao nao tira a linha em branco
+        if nova_linha || !matches!(token, Token::Comentario(_)) {
+            fechou_definicao = false;
+        }
+        match token {
+            Token::ParenAbr => {
+                let citacao = i > 0 && matches!(tokens[i - 1].0, Token::Interrogacao);
+                abertos.push((indentacao, citacao));
+            }
+            Token::ParenFec => {
+                abertos.pop();
+                fechou_definicao = abertos.is_empty();
+            }
+            _ => {}
+        }
+        saida.push_str(&texto(token));
+        fim_anterior = Some(linha_final(token, pos));
+    }
+    if !saida.is_empty() {
+        saida.push('\n');
+    }
+
+    // confere que so mexeu em espaco
+    let (formatados, _) = tokenizar_com_comentarios(&saida, arquivo);
+    let antes: Vec<String> = tokens.iter().map(|(t, _)| texto(t)).collect();
+    let depois: Vec<String> = formatados.iter().map(|(t, _)| texto(t)).collect();
+    if antes != depois {
+        return Err(vec![Diagnostico::erro(
+            format!(
+                "{}: o formatador mudaria o codigo, nada foi alterado",
+                arquivo
+            ),
+            None,
+        )]);
+    }
+    Ok(saida)
+}
+
+#[cfg(test)]
+mod testes {
+    use super::*;
+
+    fn fmt(entrada: &str) -> String {
+        formatar(entrada, "<teste>").unwrap()
+    }
+
+    #[test]
+    fn linha_em_branco_entre_definicoes() {
+        assert_eq!(
+            fmt("dobro = ( dup + )\nmain = ( 2 dobro print )\n"),
+            "dobro = ( dup + )\n\nmain = ( 2 dobro print )\n"
+        );
+    }
+
+    #[test]
+    fn comentario_no_fim_da_definicao() {
+        assert_eq!(
+            fmt("dobro ( n -- n ) = ( dup + ) # fim\nmain = ( 2 dobro print )\n"),
+            "dobro ( n -- n ) = ( dup + ) # fim\n\nmain = ( 2 dobro print )\n"
+        );
+    }
+
+    #[test]
+    fn comentario_antes_da_definicao_fica_junto() {
+        assert_eq!(
+            fmt("a = ( 1 )\n# a main\nmain = ( a print )\n"),
+            "a = ( 1 )\n\n# a main\nmain = ( a print )\n"
+        );
+    }
+
+    #[test]
+    fn indenta_o_corpo() {
+        assert_eq!(fmt("main = (\n1 print\n)\n"), "main = (\n    1 print\n)\n");
+    }
+}
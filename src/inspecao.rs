@@ -0,0 +1,244 @@
+// ---------- Inspecao ----------
+
+// --tokens, --ast e --resolved: mostram o que cada fase gerou, em texto ou json
+
+use std::collections::HashMap;
+
+use crate::json::Json;
+use crate::{
+    ASTItem, Definicao, Diagnostico, Func, No, Pos, Token, formatador, gerar_ast_tudo,
+    nome_builtin, resolver, tokenizar_tudo,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Fase {
+    Tokens,
+    Ast,
+    Resolvido,
+}
+
+fn tipo_token(token: &Token) -> &'static str {
+    match token {
+        Token::Igual => "igual",
+        Token::ParenAbr => "paren-abre",
+        Token::ParenFec => "paren-fecha",
+        Token::Interrogacao => "interrogacao",
+        Token::Exclamacao => "exclamacao",
+        Token::Mais => "mais",
+        Token::Menor => "menor",
+        Token::Maior => "maior",
+        Token::Separador => "separador",
+        Token::DoisPontos => "dois-pontos",
+        Token::Numero(_) => "numero",
+        Token::Simbolo(_) => "simbolo",
+        Token::String(_) => "string",
+        Token::Comentario(_) => "comentario",
+    }
+}
+
+fn rotulo(item: &ASTItem) -> String {
+    match item {
+        ASTItem::Numero(n) => format!("numero {}", n),
+        ASTItem::String(s) => format!("string \"{}\"", s),
+        ASTItem::FuncDef(_) => "citacao".to_owned(),
+        ASTItem::FuncCallNamed(nome) => format!("chamada {}", nome),
+        outro => nome_builtin(outro).map_or_else(|| format!("{:?}", outro), str::to_owned),
+    }
+}
+
+// onde cada nome chamado vai parar depois de carregar o arquivo
+enum Alvo<'a> {
+    Definida(&'a Pos),
+    Carregada,
+    NaoResolvida,
+}
+
+struct Tabela<'a> {
+    definidas: HashMap<&'a str, &'a Pos>,
+    carregadas: &'a HashMap<String, Func>,
+}
+
+impl Tabela<'_> {
+    fn alvo(&self, nome: &str) -> Alvo<'_> {
+        match self.definidas.get(nome) {
+            Some(pos) => Alvo::Definida(pos),
+            None if self.carregadas.contains_key(nome) => Alvo::Carregada,
+            None => Alvo::NaoResolvida,
+        }
+    }
+}
+
+fn no_json(no: &No, tabela: Option<&Tabela>) -> Json {
+    let mut pares = vec![];
+    match &no.item {
+        ASTItem::Numero(n) => {
+            pares.push(("item", "numero".into()));
+            pares.push(("valor", Json::from(*n as i64)));
+        }
+        ASTItem::String(s) => {
+            pares.push(("item", "string".into()));
+            pares.push(("valor", s.as_str().into()));
+        }
+        ASTItem::FuncDef(corpo) => {
+            pares.push(("item", "citacao".into()));
+            pares.push(("corpo", corpo_json(corpo, tabela)));
+        }
+        ASTItem::FuncCallNamed(nome) => {
+            pares.push(("item", "chamada".into()));
+            pares.push(("valor", nome.as_str().into()));
+            if let Some(tabela) = tabela {
+                let alvo = match tabela.alvo(nome) {
+                    Alvo::Definida(pos) => Json::from(pos),
+                    Alvo::Carregada => "carregada".into(),
+                    Alvo::NaoResolvida => Json::Null,
+                };
+                pares.push(("alvo", alvo));
+            }
+        }
+        outro => {
+            pares.push(("item", "builtin".into()));
+            pares.push(("valor", rotulo(outro).into()));
+        }
+    }
+    pares.push(("pos", no.pos.as_deref().into()));
+    Json::objeto(pares)
+}
+
+fn corpo_json(corpo: &Func, tabela: Option<&Tabela>) -> Json {
+    Json::Lista(corpo.iter().map(|no| no_json(no, tabela)).collect())
+}
+
+fn mostrar_corpo(corpo: &Func, nivel: usize, tabela: Option<&Tabela>) {
+    for no in corpo {
+        let pos = no
+            .pos
+            .as_ref()
+            .map_or(String::new(), |p| format!("  {}:{}", p.linha, p.coluna));
+        let alvo = match (&no.item, tabela) {
+            (ASTItem::FuncCallNamed(nome), Some(tabela)) => match tabela.alvo(nome) {
+                Alvo::Definida(pos) => format!(" -> {}", pos),
+                Alvo::Carregada => " -> carregada".to_owned(),
+                Alvo::NaoResolvida => " -> nao resolvida".to_owned(),
+            },
+            _ => String::new(),
+        };
+        println!(
+            "{}{}{}{}",
+            "    ".repeat(nivel),
+            rotulo(&no.item),
+            alvo,
+            pos
+        );
+        if let ASTItem::FuncDef(corpo) = &no.item {
+            mostrar_corpo(corpo, nivel + 1, tabela);
+        }
+    }
+}
+
+// imprime a fase pedida e devolve quantos erros teve
+pub fn inspecionar(
+    fase: Fase,
+    entrada: &str,
+    arquivo: &str,
+    carregadas: &HashMap<String, Func>,
+    json: bool,
+) -> usize {
+    let (tokens, mut diagnosticos) = tokenizar_tudo(entrada, arquivo);
+    let mut saida = vec![];
+
+    if fase == Fase::Tokens {
+        if json {
+            let lista = tokens.iter().map(|(token, pos)| {
+                Json::objeto([
+                    ("tipo", tipo_token(token).into()),
+                    ("texto", formatador::texto(token).into()),
+                    ("pos", pos.into()),
+                ])
+            });
+            saida.push(("tokens", Json::Lista(lista.collect())));
+        } else {
+            for (token, pos) in &tokens {
+                println!("{:<24} {:?}", pos.to_string(), token);
+            }
+        }
+    } else {
+        let (ast, erros) = gerar_ast_tudo(&tokens, false);
+        diagnosticos.extend(erros);
+        let mut definicoes: Vec<&Definicao> = ast.iter().collect();
+        let mut tabela = None;
+        if fase == Fase::Resolvido {
+            diagnosticos.extend(resolver(&ast, carregadas));
+            // a ultima definicao com o mesmo nome e a que fica
+            definicoes = ast
+                .iter()
+                .enumerate()
+                .filter(|(i, d)| !ast[i + 1..].iter().any(|outra| outra.nome == d.nome))
+                .map(|(_, d)| d)
+                .collect();
+            tabela = Some(Tabela {
+                definidas: definicoes
+                    .iter()
+                    .map(|d| (d.nome.as_str(), &d.pos))
+                    .collect(),
+                carregadas,
+            });
+        }
+        let tabela = tabela.as_ref();
+        // funcoes que ja estavam carregadas e o arquivo nao substitui
+        let mut extras: Vec<(&String, &Func)> = match tabela {
+            Some(tabela) => carregadas
+                .iter()
+                .filter(|(nome, _)| !tabela.definidas.contains_key(nome.as_str()))
+                .collect(),
+            None => vec![],
+        };
+        extras.sort_by_key(|(nome, _)| *nome);
+
+        if json {
+            let mut lista: Vec<Json> = definicoes
+                .iter()
+                .map(|d| {
+                    Json::objeto([
+                        ("nome", d.nome.as_str().into()),
+                        ("efeito", d.efeito.as_ref().map(|e| e.to_string()).into()),
+                        ("pos", (&d.pos).into()),
+                        ("corpo", corpo_json(&d.corpo, tabela)),
+                    ])
+                })
+                .collect();
+            lista.extend(extras.iter().map(|(nome, corpo)| {
+                Json::objeto([
+                    ("nome", nome.as_str().into()),
+                    ("carregada", true.into()),
+                    ("corpo", corpo_json(corpo, tabela)),
+                ])
+            }));
+            saida.push(("definicoes", Json::Lista(lista)));
+        } else {
+            for definicao in &definicoes {
+                let efeito = definicao
+                    .efeito
+                    .as_ref()
+                    .map_or(String::new(), |e| format!(" {}", e));
+                println!("{}{}  {}", definicao.nome, efeito, definicao.pos);
+                mostrar_corpo(&definicao.corpo, 1, tabela);
+            }
+            for (nome, corpo) in &extras {
+                println!("{}  carregada", nome);
+                mostrar_corpo(corpo, 1, tabela);
+            }
+        }
+    }
+
+    if json {
+        let lista = diagnosticos.iter().map(Json::from).collect();
+        saida.push(("diagnosticos", Json::Lista(lista)));
+        println!("{}", Json::objeto(saida));
+    } else {
+        diagnosticos.iter().for_each(Diagnostico::mostrar);
+    }
+    diagnosticos
+        .iter()
+        .filter(|d| d.nivel == crate::Nivel::Erro)
+        .count()
+}
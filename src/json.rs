@@ -0,0 +1,413 @@
+// ---------- Json ----------
+
+// so o suficiente pra saida das ferramentas e pras mensagens do dap e do
+// lsp, sem dependencia
+
+use std::fmt;
+use std::io::{self, BufRead, Read, Write};
+
+use crate::{Diagnostico, Nivel, Pos};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Numero(i64),
+    Texto(String),
+    Lista(Vec<Json>),
+    // lista pra manter a ordem das chaves
+    Objeto(Vec<(String, Json)>),
+}
+
+impl Json {
+    pub fn objeto<'a>(pares: impl IntoIterator<Item = (&'a str, Json)>) -> Json {
+        Json::Objeto(
+            pares
+                .into_iter()
+                .map(|(chave, valor)| (chave.to_owned(), valor))
+                .collect(),
+        )
+    }
+
+    pub fn ler(texto: &str) -> Result<Json, String> {
+        let mut leitor = Leitor {
+            texto: texto.as_bytes(),
+            i: 0,
+        };
+        let valor = leitor.valor()?;
+        leitor.espacos();
+        if leitor.i < texto.len() {
+            return Err(format!(
+                "sobrou texto depois do json na posicao {}",
+                leitor.i
+            ));
+        }
+        Ok(valor)
+    }
+
+    // Null quando nao e objeto ou nao tem a chave, pra encadear
+    pub fn get(&self, chave: &str) -> &Json {
+        match self {
+            Json::Objeto(pares) => pares
+                .iter()
+                .find(|(c, _)| c == chave)
+                .map_or(&Json::Null, |(_, valor)| valor),
+            _ => &Json::Null,
+        }
+    }
+
+    pub fn texto(&self) -> Option<&str> {
+        match self {
+            Json::Texto(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn numero(&self) -> Option<i64> {
+        match self {
+            Json::Numero(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    pub fn bool(&self) -> Option<bool> {
+        match self {
+            Json::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn lista(&self) -> &[Json] {
+        match self {
+            Json::Lista(itens) => itens,
+            _ => &[],
+        }
+    }
+}
+
+struct Leitor<'a> {
+    texto: &'a [u8],
+    i: usize,
+}
+
+impl Leitor<'_> {
+    fn espacos(&mut self) {
+        while self.texto.get(self.i).is_some_and(u8::is_ascii_whitespace) {
+            self.i += 1;
+        }
+    }
+
+    fn esperar(&mut self, c: u8) -> Result<(), String> {
+        self.espacos();
+        if self.texto.get(self.i) != Some(&c) {
+            return Err(format!("esperava `{}` na posicao {}", c as char, self.i));
+        }
+        self.i += 1;
+        Ok(())
+    }
+
+    fn palavra(&mut self, palavra: &str, valor: Json) -> Result<Json, String> {
+        if !self.texto[self.i..].starts_with(palavra.as_bytes()) {
+            return Err(format!("valor invalido na posicao {}", self.i));
+        }
+        self.i += palavra.len();
+        Ok(valor)
+    }
+
+    fn valor(&mut self) -> Result<Json, String> {
+        self.espacos();
+        match self.texto.get(self.i) {
+            None => Err("json acabou antes da hora".to_owned()),
+            Some(b'n') => self.palavra("null", Json::Null),
+            Some(b't') => self.palavra("true", Json::Bool(true)),
+            Some(b'f') => self.palavra("false", Json::Bool(false)),
+            Some(b'"') => self.texto().map(Json::Texto),
+            Some(b'[') => {
+                self.i += 1;
+                let mut itens = vec![];
+                self.espacos();
+                if self.texto.get(self.i) == Some(&b']') {
+                    self.i += 1;
+                    return Ok(Json::Lista(itens));
+                }
+                loop {
+                    itens.push(self.valor()?);
+                    self.espacos();
+                    match self.texto.get(self.i) {
+                        Some(b',') => self.i += 1,
+                        _ => break,
+                    }
+                }
+                self.esperar(b']')?;
+                Ok(Json::Lista(itens))
+            }
+            Some(b'{') => {
+                self.i += 1;
+                let mut pares = vec![];
+                self.espacos();
+                if self.texto.get(self.i) == Some(&b'}') {
+                    self.i += 1;
+                    return Ok(Json::Objeto(pares));
+                }
+                loop {
+                    self.espacos();
+                    let chave = self.texto()?;
+                    self.esperar(b':')?;
+                    pares.push((chave, self.valor()?));
+                    self.espacos();
+                    match self.texto.get(self.i) {
+                        Some(b',') => self.i += 1,
+                        _ => break,
+                    }
+                }
+                self.esperar(b'}')?;
+                Ok(Json::Objeto(pares))
+            }
+            Some(_) => self.numero(),
+        }
+    }
+
+    // numero com fracao ou expoente e truncado, aqui so tem inteiro
+    fn numero(&mut self) -> Result<Json, String> {
+        let inicio = self.i;
+        while self
+            .texto
+            .get(self.i)
+            .is_some_and(|c| c.is_ascii_digit() || b"-+.eE".contains(c))
+        {
+            self.i += 1;
+        }
+        let numero = str::from_utf8(&self.texto[inicio..self.i]).unwrap();
+        match numero.parse::<i64>() {
+            Ok(n) => Ok(Json::Numero(n)),
+            Err(_) => match numero.parse::<f64>() {
+                Ok(n) => Ok(Json::Numero(n as i64)),
+                Err(_) => Err(format!("valor invalido na posicao {}", inicio)),
+            },
+        }
+    }
+
+    fn texto(&mut self) -> Result<String, String> {
+        if self.texto.get(self.i) != Some(&b'"') {
+            return Err(format!("esperava string na posicao {}", self.i));
+        }
+        self.i += 1;
+        let mut bytes = vec![];
+        loop {
+            let Some(&c) = self.texto.get(self.i) else {
+                return Err("string sem fim".to_owned());
+            };
+            self.i += 1;
+            match c {
+                b'"' => break,
+                b'\\' => {
+                    let Some(&escape) = self.texto.get(self.i) else {
+                        return Err("string sem fim".to_owned());
+                    };
+                    self.i += 1;
+                    let c = match escape {
+                        b'n' => '\n',
+                        b't' => '\t',
+                        b'r' => '\r',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'u' => self.unicode()?,
+                        outro => outro as char,
+                    };
+                    bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                }
+                c => bytes.push(c),
+            }
+        }
+        String::from_utf8(bytes).map_err(|_| "string com utf-8 invalido".to_owned())
+    }
+
+    fn hex(&mut self) -> Result<u32, String> {
+        let hex = self
+            .texto
+            .get(self.i..self.i + 4)
+            .and_then(|h| str::from_utf8(h).ok())
+            .and_then(|h| u32::from_str_radix(h, 16).ok())
+            .ok_or_else(|| format!("\\u invalido na posicao {}", self.i))?;
+        self.i += 4;
+        Ok(hex)
+    }
+
+    // \uXXXX, com o par de surrogates pra fora do plano basico
+    fn unicode(&mut self) -> Result<char, String> {
+        let mut codigo = self.hex()?;
+        if (0xD800..0xDC00).contains(&codigo) && self.texto[self.i..].starts_with(b"\\u") {
+            self.i += 2;
+            let baixo = self.hex()?;
+            codigo = 0x10000 + ((codigo - 0xD800) << 10) + (baixo.wrapping_sub(0xDC00) & 0x3FF);
+        }
+        Ok(char::from_u32(codigo).unwrap_or(char::REPLACEMENT_CHARACTER))
+    }
+}
+
+// mensagens do dap e do lsp: cabecalho Content-Length, linha em branco e o json
+
+// maior Content-Length aceito
+const TAMANHO_MAXIMO: usize = 64 << 20;
+
+// None quando a entrada acaba
+pub fn ler_mensagem(entrada: &mut impl BufRead) -> io::Result<Option<Json>> {
+    let mut tamanho = None;
+    loop {
+        let mut linha = String::new();
+        if entrada.read_line(&mut linha)? == 0 {
+            return Ok(None);
+        }
+        let linha = linha.trim_end();
+        if linha.is_empty() {
+            if tamanho.is_some() {
+                break;
+            }
+            continue;
+        }
+        if let Some((nome, valor)) = linha.split_once(':')
+            && nome.eq_ignore_ascii_case("content-length")
+        {
+            tamanho = valor.trim().parse::<usize>().ok();
+        }
+    }
+    let tamanho = tamanho.unwrap();
+    if tamanho > TAMANHO_MAXIMO {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "mensagem de {} bytes, o maximo e {}",
+                tamanho, TAMANHO_MAXIMO
+            ),
+        ));
+    }
+    // o buffer cresce com o que chega, nao com o que o cabecalho diz
+    let mut corpo = vec![];
+    entrada
+        .by_ref()
+        .take(tamanho as u64)
+        .read_to_end(&mut corpo)?;
+    if corpo.len() < tamanho {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    let texto = String::from_utf8_lossy(&corpo);
+    Json::ler(&texto)
+        .map(Some)
+        .map_err(|erro| io::Error::new(io::ErrorKind::InvalidData, erro))
+}
+
+pub fn escrever_mensagem(saida: &mut impl Write, mensagem: &Json) -> io::Result<()> {
+    let texto = mensagem.to_string();
+    write!(saida, "Content-Length: {}\r\n\r\n{}", texto.len(), texto)?;
+    saida.flush()
+}
+
+impl From<bool> for Json {
+    fn from(b: bool) -> Json {
+        Json::Bool(b)
+    }
+}
+
+impl From<i64> for Json {
+    fn from(n: i64) -> Json {
+        Json::Numero(n)
+    }
+}
+
+impl From<usize> for Json {
+    fn from(n: usize) -> Json {
+        Json::Numero(n as i64)
+    }
+}
+
+impl From<&str> for Json {
+    fn from(s: &str) -> Json {
+        Json::Texto(s.to_owned())
+    }
+}
+
+impl From<String> for Json {
+    fn from(s: String) -> Json {
+        Json::Texto(s)
+    }
+}
+
+impl<T: Into<Json>> From<Option<T>> for Json {
+    fn from(opcao: Option<T>) -> Json {
+        opcao.map_or(Json::Null, Into::into)
+    }
+}
+
+impl From<&Pos> for Json {
+    fn from(pos: &Pos) -> Json {
+        Json::objeto([
+            ("arquivo", Json::from(&*pos.arquivo)),
+            ("linha", pos.linha.into()),
+            ("coluna", pos.coluna.into()),
+        ])
+    }
+}
+
+impl From<&Diagnostico> for Json {
+    fn from(diagnostico: &Diagnostico) -> Json {
+        let nivel = match diagnostico.nivel {
+            Nivel::Erro => "erro",
+            Nivel::Aviso => "aviso",
+        };
+        Json::objeto([
+            ("nivel", nivel.into()),
+            ("mensagem", diagnostico.mensagem.as_str().into()),
+            ("pos", diagnostico.pos.as_ref().into()),
+            ("sugestao", diagnostico.sugestao.as_deref().into()),
+        ])
+    }
+}
+
+fn escrever_texto(f: &mut fmt::Formatter, s: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+// compacto, uma linha so
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(b) => write!(f, "{}", b),
+            Json::Numero(n) => write!(f, "{}", n),
+            Json::Texto(s) => escrever_texto(f, s),
+            Json::Lista(itens) => {
+                write!(f, "[")?;
+                for (i, item) in itens.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            }
+            Json::Objeto(pares) => {
+                write!(f, "{{")?;
+                for (i, (chave, valor)) in pares.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    escrever_texto(f, chave)?;
+                    write!(f, ":{}", valor)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
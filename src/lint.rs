@@ -0,0 +1,283 @@
+// ---------- Lint ----------
+
+// avisos de coisas que funcionam mas quase sempre sao engano, cada regra tem
+// um nome pra poder desligar no arquivo com `# lint: -nome` (ou ligar com +nome)
+
+use std::collections::HashSet;
+
+use crate::{AST, ASTItem, Diagnostico, Func, No, Pos};
+
+pub struct Regra {
+    pub nome: &'static str,
+    pub descricao: &'static str,
+    checar: fn(&AST, &mut Vec<Diagnostico>),
+}
+
+pub const REGRAS: &[Regra] = &[
+    Regra {
+        nome: "nao-usada",
+        descricao: "funcao que ninguem chama",
+        checar: nao_usada,
+    },
+    Regra {
+        nome: "else-vazio",
+        descricao: "if com ?() no ramo falso",
+        checar: else_vazio,
+    },
+    Regra {
+        nome: "swap-pop",
+        descricao: "swap pop swap pop no lugar de 2 swapn pop pop",
+        checar: swap_pop,
+    },
+    Regra {
+        nome: "inalcancavel",
+        descricao: "codigo depois de uma recursao que nunca volta",
+        checar: inalcancavel,
+    },
+    Regra {
+        nome: "swapn-constante",
+        descricao: "0 swapn nao faz nada e 1 swapn e so swap",
+        checar: swapn_constante,
+    },
+];
+
+// roda as regras ligadas no arquivo, o texto e lido so pelas diretivas
+pub fn lint(ast: &AST, entrada: &str, arquivo: &str) -> Vec<Diagnostico> {
+    let mut ligadas: HashSet<&str> = REGRAS.iter().map(|r| r.nome).collect();
+    let mut diagnosticos = vec![];
+    for (linha, texto) in entrada.lines().enumerate() {
+        let Some(diretiva) = texto.trim_start().strip_prefix('#') else {
+            continue;
+        };
+        let Some(nomes) = diretiva.trim_start().strip_prefix("lint:") else {
+            continue;
+        };
+        let pos = Pos {
+            arquivo: arquivo.into(),
+            linha: linha + 1,
+            coluna: 1,
+        };
+        for nome in nomes.split_whitespace() {
+            let (ligar, nome) = match nome.split_at_checked(1) {
+                Some(("+", nome)) => (true, nome),
+                Some(("-", nome)) => (false, nome),
+                _ => (true, nome),
+            };
+            let Some(regra) = REGRAS.iter().find(|r| r.nome == nome) else {
+                diagnosticos.push(Diagnostico::aviso(
+                    format!("regra de lint `{}` nao existe", nome),
+                    Some(pos.clone()),
+                ));
+                continue;
+            };
+            if ligar {
+                ligadas.insert(regra.nome);
+            } else {
+                ligadas.remove(regra.nome);
+            }
+        }
+    }
+
+    for regra in REGRAS.iter().filter(|r| ligadas.contains(r.nome)) {
+        let mut achados = vec![];
+        (regra.checar)(ast, &mut achados);
+        diagnosticos.extend(achados.into_iter().map(|mut d| {
+            d.mensagem = format!("[{}] {}", regra.nome, d.mensagem);
+            d
+        }));
+    }
+    diagnosticos.sort_by_key(|d| d.pos.as_ref().map(|p| (p.linha, p.coluna)));
+    diagnosticos
+}
+
+// cada sequencia de nos do programa, o corpo das funcoes e de cada citacao
+fn sequencias<'a>(func: &'a Func, f: &mut impl FnMut(&'a Func)) {
+    f(func);
+    for no in func {
+        if let ASTItem::FuncDef(corpo) = &no.item {
+            sequencias(corpo, f);
+        }
+    }
+}
+
+fn janelas<'a>(ast: &'a AST, tam: usize, f: &mut impl FnMut(&'a [No])) {
+    for definicao in ast {
+        sequencias(&definicao.corpo, &mut |func| {
+            func.windows(tam).for_each(&mut *f)
+        });
+    }
+}
+
+fn nao_usada(ast: &AST, diagnosticos: &mut Vec<Diagnostico>) {
+    let mut chamadas = HashSet::new();
+    for definicao in ast {
+        crate::visitar_nos(&definicao.corpo, &mut |no| {
+            if let ASTItem::FuncCallNamed(nome) = &no.item
+                && *nome != definicao.nome
+            {
+                chamadas.insert(nome.as_str());
+            }
+        });
+    }
+    for definicao in ast {
+        // main e os testes sao chamados de fora
+        if definicao.nome != "main"
+            && !definicao.nome.starts_with(crate::teste::PREFIXO)
+            && !chamadas.contains(definicao.nome.as_str())
+        {
+            diagnosticos.push(
+                Diagnostico::aviso(
+                    format!("funcao `{}` nunca e chamada", definicao.nome),
+                    Some(definicao.pos.clone()),
+                )
+                .com_sugestao(Some("apague a definicao ou chame ela".to_owned())),
+            );
+        }
+    }
+}
+
+fn else_vazio(ast: &AST, diagnosticos: &mut Vec<Diagnostico>) {
+    janelas(ast, 3, &mut |nos| {
+        if let [_, vazio, ASTItem::If] = [&nos[0].item, &nos[1].item, &nos[2].item]
+            && matches!(vazio, ASTItem::FuncDef(f) if f.is_empty())
+        {
+            diagnosticos.push(
+                Diagnostico::aviso(
+                    "ramo falso do if vazio".to_owned(),
+                    nos[1].pos.as_deref().cloned(),
+                )
+                .com_sugestao(Some(
+                    "o ramo falso deixa na stack o que a comparacao deixou, confira se \
+                         nao devia tirar"
+                        .to_owned(),
+                )),
+            );
+        }
+    });
+}
+
+fn swap_pop(ast: &AST, diagnosticos: &mut Vec<Diagnostico>) {
+    janelas(ast, 4, &mut |nos| {
+        if let [ASTItem::Swap, ASTItem::Pop, ASTItem::Swap, ASTItem::Pop] =
+            [&nos[0].item, &nos[1].item, &nos[2].item, &nos[3].item]
+        {
+            diagnosticos.push(
+                Diagnostico::aviso(
+                    "`swap pop swap pop` tira os dois itens abaixo do topo".to_owned(),
+                    nos[0].pos.as_deref().cloned(),
+                )
+                .com_sugestao(Some("troque por `2 swapn pop pop`".to_owned())),
+            );
+        }
+    });
+}
+
+fn inalcancavel(ast: &AST, diagnosticos: &mut Vec<Diagnostico>) {
+    for definicao in ast {
+        let corpo = &definicao.corpo;
+        let recursao = corpo.iter().position(
+            |no| matches!(&no.item, ASTItem::FuncCallNamed(nome) if *nome == definicao.nome),
+        );
+        if let Some(depois) = recursao.and_then(|i| corpo.get(i + 1)) {
+            diagnosticos.push(
+                Diagnostico::aviso(
+                    format!(
+                        "codigo depois da chamada de `{}` nunca roda, a recursao nao tem fim",
+                        definicao.nome
+                    ),
+                    depois.pos.as_deref().cloned(),
+                )
+                .com_sugestao(Some(
+                    "coloque a chamada recursiva dentro de um ramo do if".to_owned(),
+                )),
+            );
+        }
+    }
+}
+
+fn swapn_constante(ast: &AST, diagnosticos: &mut Vec<Diagnostico>) {
+    janelas(ast, 2, &mut |nos| {
+        let (mensagem, sugestao) = match [&nos[0].item, &nos[1].item] {
+            [ASTItem::Numero(0), ASTItem::SwapN] => ("`0 swapn` nao faz nada", "apague"),
+            [ASTItem::Numero(1), ASTItem::SwapN] => {
+                ("`1 swapn` e o mesmo que `swap`", "troque por `swap`")
+            }
+            _ => return,
+        };
+        diagnosticos.push(
+            Diagnostico::aviso(mensagem.to_owned(), nos[0].pos.as_deref().cloned())
+                .com_sugestao(Some(sugestao.to_owned())),
+        );
+    });
+}
+
+#[cfg(test)]
+mod testes {
+    use super::*;
+    use crate::analisar;
+
+    fn avisos(fonte: &str) -> Vec<String> {
+        let (ast, _) = analisar(fonte, "<teste>");
+        lint(&ast, fonte, "<teste>")
+            .into_iter()
+            .map(|d| d.mensagem)
+            .collect()
+    }
+
+    #[test]
+    fn nao_usada() {
+        assert_eq!(
+            avisos("f = ( 1 )\nmain = ( 2 print )"),
+            ["[nao-usada] funcao `f` nunca e chamada"]
+        );
+        assert!(avisos("f = ( 1 )\nmain = ( f print )").is_empty());
+    }
+
+    #[test]
+    fn else_vazio() {
+        assert_eq!(
+            avisos("main = ( true ?( 1 print ) ?( ) if )"),
+            ["[else-vazio] ramo falso do if vazio"]
+        );
+    }
+
+    #[test]
+    fn swap_pop() {
+        assert_eq!(
+            avisos("main = ( 1 2 3 swap pop swap pop print )"),
+            ["[swap-pop] `swap pop swap pop` tira os dois itens abaixo do topo"]
+        );
+    }
+
+    #[test]
+    fn inalcancavel() {
+        assert_eq!(
+            avisos("f = ( 1 print f 2 )\nmain = ( f )"),
+            ["[inalcancavel] codigo depois da chamada de `f` nunca roda, a recursao nao tem fim"]
+        );
+    }
+
+    #[test]
+    fn swapn_constante() {
+        assert_eq!(
+            avisos("main = ( 1 2 0 swapn 1 swapn print )"),
+            [
+                "[swapn-constante] `0 swapn` nao faz nada",
+                "[swapn-constante] `1 swapn` e o mesmo que `swap`"
+            ]
+        );
+    }
+
+    #[test]
+    fn diretiva_desliga_e_liga() {
+        let fonte = "# lint: -nao-usada\nf = ( 1 )\nmain = ( 1 0 swapn print )";
+        assert_eq!(avisos(fonte), ["[swapn-constante] `0 swapn` nao faz nada"]);
+        let fonte =
+            "# lint: -swapn-constante -nao-usada +nao-usada\nf = ( 1 )\nmain = ( 1 0 swapn )";
+        assert_eq!(avisos(fonte), ["[nao-usada] funcao `f` nunca e chamada"]);
+        assert_eq!(
+            avisos("# lint: -nada\nmain = ( 1 )"),
+            ["regra de lint `nada` nao existe"]
+        );
+    }
+}
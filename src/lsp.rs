@@ -0,0 +1,354 @@
+// ---------- Lsp ----------
+
+// servidor do language server protocol no stdin/stdout: os diagnosticos do
+// check a cada mudanca, ir pra definicao e achar as chamadas das funcoes
+// nomeadas, hover com o corpo e o efeito, completar palavras e a lista de
+// definicoes do arquivo. O editor manda o texto inteiro a cada mudanca.
+
+use std::collections::HashMap;
+use std::io;
+
+use crate::json::{Json, escrever_mensagem, ler_mensagem};
+use crate::{
+    AST, ASTItem, Definicao, Diagnostico, Nivel, PALAVRAS, PSFState, Pos, analisar, checagem,
+    formatar_func, visitar_nos,
+};
+
+// tipos de item do protocolo
+const SIMBOLO_FUNCAO: i64 = 12;
+const COMPLETAR_FUNCAO: i64 = 3;
+const COMPLETAR_PALAVRA: i64 = 14;
+const METODO_DESCONHECIDO: i64 = -32601;
+
+fn enviar(mut pares: Vec<(&str, Json)>) {
+    pares.insert(0, ("jsonrpc", "2.0".into()));
+    // stdout fechado e o editor que foi embora
+    if escrever_mensagem(&mut io::stdout().lock(), &Json::objeto(pares)).is_err() {
+        std::process::exit(0);
+    }
+}
+
+fn responder(id: &Json, resultado: Json) {
+    enviar(vec![("id", id.clone()), ("result", resultado)]);
+}
+
+fn recusar(id: &Json, codigo: i64, mensagem: &str) {
+    let erro = Json::objeto([("code", Json::Numero(codigo)), ("message", mensagem.into())]);
+    enviar(vec![("id", id.clone()), ("error", erro)]);
+}
+
+fn notificar(metodo: &str, parametros: Json) {
+    enviar(vec![("method", metodo.into()), ("params", parametros)]);
+}
+
+// file:///a/b.psf -> /a/b.psf, so pras mensagens
+fn arquivo(uri: &str) -> &str {
+    uri.strip_prefix("file://").unwrap_or(uri)
+}
+
+// o lsp conta a coluna em utf-16 a partir de 0, o Pos em chars a partir de 1
+fn posicao(texto: &str, linha: usize, coluna: usize) -> Json {
+    let caracter: usize = texto
+        .lines()
+        .nth(linha.saturating_sub(1))
+        .unwrap_or("")
+        .chars()
+        .take(coluna.saturating_sub(1))
+        .map(char::len_utf16)
+        .sum();
+    Json::objeto([
+        ("line", linha.saturating_sub(1).into()),
+        ("character", caracter.into()),
+    ])
+}
+
+fn intervalo(texto: &str, pos: &Pos, tam: usize) -> Json {
+    Json::objeto([
+        ("start", posicao(texto, pos.linha, pos.coluna)),
+        ("end", posicao(texto, pos.linha, pos.coluna + tam)),
+    ])
+}
+
+// o contrario do posicao, linha e coluna como no Pos
+fn cursor(texto: &str, posicao: &Json) -> (usize, usize) {
+    let linha = posicao.get("line").numero().unwrap_or(0) as usize;
+    let caracter = posicao.get("character").numero().unwrap_or(0) as usize;
+    let mut unidades = 0;
+    let coluna = texto
+        .lines()
+        .nth(linha)
+        .unwrap_or("")
+        .chars()
+        .take_while(|c| {
+            unidades += c.len_utf16();
+            unidades <= caracter
+        })
+        .count();
+    (linha + 1, coluna + 1)
+}
+
+// ate o fim da palavra que comeca no pos, pra sublinhar ela toda
+fn tamanho_palavra(texto: &str, pos: &Pos) -> usize {
+    let linha = texto.lines().nth(pos.linha.saturating_sub(1)).unwrap_or("");
+    let tam = linha
+        .chars()
+        .skip(pos.coluna.saturating_sub(1))
+        .take_while(|c| !c.is_whitespace() && !matches!(c, '(' | ')'))
+        .count();
+    tam.max(1)
+}
+
+fn diagnostico_json(texto: &str, diagnostico: &Diagnostico) -> Json {
+    let intervalo = match &diagnostico.pos {
+        Some(pos) => intervalo(texto, pos, tamanho_palavra(texto, pos)),
+        None => Json::objeto([
+            ("start", posicao(texto, 1, 1)),
+            ("end", posicao(texto, 1, 1)),
+        ]),
+    };
+    let mensagem = match &diagnostico.sugestao {
+        Some(sugestao) => format!("{}\najuda: {}", diagnostico.mensagem, sugestao),
+        None => diagnostico.mensagem.clone(),
+    };
+    let severidade = match diagnostico.nivel {
+        Nivel::Erro => 1,
+        Nivel::Aviso => 2,
+    };
+    Json::objeto([
+        ("range", intervalo),
+        ("severity", Json::Numero(severidade)),
+        ("source", "psf".into()),
+        ("message", mensagem.into()),
+    ])
+}
+
+fn publicar(base: &PSFState, uri: &str, texto: &str) {
+    let diagnosticos = base
+        .diagnosticar(texto, arquivo(uri))
+        .iter()
+        .map(|d| diagnostico_json(texto, d))
+        .collect();
+    notificar(
+        "textDocument/publishDiagnostics",
+        Json::objeto([
+            ("uri", uri.into()),
+            ("diagnostics", Json::Lista(diagnosticos)),
+        ]),
+    );
+}
+
+// a funcao nomeada sob o cursor, no nome da definicao ou numa chamada
+fn nome_em(ast: &AST, linha: usize, coluna: usize) -> Option<&str> {
+    // o cursor logo depois do nome ainda conta
+    let dentro = |pos: &Pos, nome: &str| {
+        pos.linha == linha && (pos.coluna..=pos.coluna + nome.chars().count()).contains(&coluna)
+    };
+    let mut achado = None;
+    for definicao in ast {
+        if dentro(&definicao.pos, &definicao.nome) {
+            return Some(&definicao.nome);
+        }
+        visitar_nos(&definicao.corpo, &mut |no| {
+            if let (ASTItem::FuncCallNamed(nome), Some(pos)) = (&no.item, &no.pos)
+                && dentro(pos, nome)
+            {
+                achado = Some(nome.as_str());
+            }
+        });
+        if achado.is_some() {
+            return achado;
+        }
+    }
+    None
+}
+
+// definida de novo, vale a ultima
+fn definicao<'a>(ast: &'a AST, nome: &str) -> Option<&'a Definicao> {
+    ast.iter().rev().find(|definicao| definicao.nome == nome)
+}
+
+fn chamadas<'a>(ast: &'a AST, nome: &str) -> Vec<&'a Pos> {
+    let mut posicoes = vec![];
+    for definicao in ast {
+        visitar_nos(&definicao.corpo, &mut |no| {
+            if let (ASTItem::FuncCallNamed(chamada), Some(pos)) = (&no.item, &no.pos)
+                && chamada == nome
+            {
+                posicoes.push(&**pos);
+            }
+        });
+    }
+    posicoes
+}
+
+// o corpo numa linha e o efeito declarado ou, sem ele, o que o check infere
+fn hover(base: &PSFState, ast: &AST, nome: &str) -> Option<Json> {
+    let (declarado, corpo) = match definicao(ast, nome) {
+        Some(definicao) => (definicao.efeito.clone(), &definicao.corpo),
+        None => (None, base.funcoes.get(nome)?),
+    };
+    let (efeito, nota) = match declarado {
+        Some(efeito) => (format!(" {}", efeito), "efeito declarado"),
+        None => match checagem::efeito_inferido(ast, &base.funcoes, nome) {
+            Some(efeito) => (format!(" {}", efeito), "efeito inferido"),
+            None => (String::new(), "efeito desconhecido"),
+        },
+    };
+    let texto = format!(
+        "```\n{}{} = ( {} )\n```\n{}",
+        nome,
+        efeito,
+        formatar_func(corpo),
+        nota
+    );
+    let conteudo = Json::objeto([("kind", "markdown".into()), ("value", texto.into())]);
+    Some(Json::objeto([("contents", conteudo)]))
+}
+
+// o editor filtra pelo que ja foi digitado
+fn completar(base: &PSFState, ast: &AST) -> Json {
+    let mut itens: Vec<Json> = PALAVRAS
+        .iter()
+        .map(|(nome, _)| {
+            Json::objeto([
+                ("label", (*nome).into()),
+                ("kind", Json::Numero(COMPLETAR_PALAVRA)),
+            ])
+        })
+        .collect();
+    let mut nomes: Vec<&str> = ast.iter().map(|d| d.nome.as_str()).collect();
+    nomes.extend(base.funcoes.keys().map(String::as_str));
+    nomes.sort();
+    nomes.dedup();
+    for nome in nomes {
+        let mut pares = vec![
+            ("label", nome.into()),
+            ("kind", Json::Numero(COMPLETAR_FUNCAO)),
+        ];
+        // detail null confunde alguns editores
+        if let Some(efeito) = definicao(ast, nome).and_then(|d| d.efeito.as_ref()) {
+            pares.push(("detail", efeito.to_string().into()));
+        }
+        itens.push(Json::objeto(pares));
+    }
+    Json::Lista(itens)
+}
+
+fn consultar(base: &PSFState, metodo: &str, uri: &str, texto: &str, parametros: &Json) -> Json {
+    let (ast, _) = analisar(texto, arquivo(uri));
+    let local = |pos: &Pos, nome: &str| {
+        Json::objeto([
+            ("uri", uri.into()),
+            ("range", intervalo(texto, pos, nome.chars().count())),
+        ])
+    };
+    let (linha, coluna) = cursor(texto, parametros.get("position"));
+    let nome = nome_em(&ast, linha, coluna);
+    match metodo {
+        "textDocument/definition" => nome
+            .and_then(|nome| definicao(&ast, nome))
+            .map(|d| local(&d.pos, &d.nome))
+            .into(),
+        "textDocument/references" => {
+            let Some(nome) = nome else {
+                return Json::Null;
+            };
+            let mut locais = vec![];
+            if parametros.get("context").get("includeDeclaration").bool() == Some(true) {
+                locais.extend(
+                    ast.iter()
+                        .filter(|d| d.nome == nome)
+                        .map(|d| local(&d.pos, nome)),
+                );
+            }
+            locais.extend(chamadas(&ast, nome).into_iter().map(|pos| local(pos, nome)));
+            Json::Lista(locais)
+        }
+        "textDocument/hover" => nome.and_then(|nome| hover(base, &ast, nome)).into(),
+        "textDocument/completion" => completar(base, &ast),
+        "textDocument/documentSymbol" => Json::Lista(
+            ast.iter()
+                .map(|d| {
+                    Json::objeto([
+                        ("name", d.nome.as_str().into()),
+                        ("kind", Json::Numero(SIMBOLO_FUNCAO)),
+                        ("location", local(&d.pos, &d.nome)),
+                    ])
+                })
+                .collect(),
+        ),
+        _ => Json::Null,
+    }
+}
+
+pub fn servir(base: &PSFState) {
+    let mut documentos: HashMap<String, String> = HashMap::new();
+    loop {
+        let mensagem = match ler_mensagem(&mut io::stdin().lock()) {
+            Ok(Some(mensagem)) => mensagem,
+            Ok(None) => return,
+            Err(erro) => {
+                eprintln!("lsp: mensagem invalida: {}", erro);
+                continue;
+            }
+        };
+        let id = mensagem.get("id");
+        let parametros = mensagem.get("params");
+        let documento = parametros.get("textDocument");
+        let uri = documento.get("uri").texto().unwrap_or("");
+        let metodo = mensagem.get("method").texto().unwrap_or("");
+        match metodo {
+            "initialize" => {
+                let capacidades = Json::objeto([
+                    // 1: o texto inteiro a cada mudanca
+                    ("textDocumentSync", Json::Numero(1)),
+                    ("definitionProvider", true.into()),
+                    ("referencesProvider", true.into()),
+                    ("hoverProvider", true.into()),
+                    ("completionProvider", Json::objeto([])),
+                    ("documentSymbolProvider", true.into()),
+                ]);
+                let servidor = Json::objeto([("name", "prog_sta_func".into())]);
+                responder(
+                    id,
+                    Json::objeto([("capabilities", capacidades), ("serverInfo", servidor)]),
+                );
+            }
+            "shutdown" => responder(id, Json::Null),
+            "exit" => return,
+            "textDocument/didOpen" => {
+                let texto = documento.get("text").texto().unwrap_or("").to_owned();
+                publicar(base, uri, &texto);
+                documentos.insert(uri.to_owned(), texto);
+            }
+            "textDocument/didChange" => {
+                let mudancas = parametros.get("contentChanges").lista();
+                if let Some(texto) = mudancas.last().and_then(|m| m.get("text").texto()) {
+                    publicar(base, uri, texto);
+                    documentos.insert(uri.to_owned(), texto.to_owned());
+                }
+            }
+            "textDocument/didClose" => {
+                documentos.remove(uri);
+                notificar(
+                    "textDocument/publishDiagnostics",
+                    Json::objeto([("uri", uri.into()), ("diagnostics", Json::Lista(vec![]))]),
+                );
+            }
+            "textDocument/definition"
+            | "textDocument/references"
+            | "textDocument/hover"
+            | "textDocument/completion"
+            | "textDocument/documentSymbol" => {
+                let resultado = match documentos.get(uri) {
+                    Some(texto) => consultar(base, metodo, uri, texto, parametros),
+                    None => Json::Null,
+                };
+                responder(id, resultado);
+            }
+            // notificacao que nao interessa nao tem resposta
+            _ if *id == Json::Null => {}
+            _ => recusar(id, METODO_DESCONHECIDO, "metodo nao suportado"),
+        }
+    }
+}
@@ -1,6 +1,23 @@
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
 use std::io::Write;
-use std::{env, fs, io, str};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+use std::{env, fmt, fs, io, str};
+
+mod checagem;
+mod cobertura;
+mod dap;
+mod depurador;
+mod explicacao;
+mod formatador;
+mod inspecao;
+mod json;
+mod lint;
+mod lsp;
+mod perfil;
+mod rastreio;
+mod teste;
 
 // ---------- TODO ----------
 
@@ -18,6 +35,13 @@ macro_rules! log_info {
     };
 }
 
+macro_rules! log_aviso {
+    ( $($arg:tt)* ) => {
+        print!("\x1b[1;33maviso\x1b[0m: ");
+        println!($($arg)*);
+    };
+}
+
 macro_rules! log_error {
     // () => {
     //     println!("\x1b[1;31merro\x1b[0m");
@@ -30,9 +54,23 @@ macro_rules! log_error {
     }};
 }
 
+// erro no codigo fonte, com a posicao separada da mensagem
+macro_rules! erro_sintaxe {
+    ( $pos:expr, $($arg:tt)* ) => {
+        Diagnostico::erro(format!($($arg)*), Some($pos.clone()))
+    };
+}
+
+// erro recuperavel dentro do interpretador, pode ser pego por um try
+macro_rules! lancar {
+    ( $tipo:expr, $($arg:tt)* ) => {
+        return Err(ErroPSF::new($tipo, format!($($arg)*)))
+    };
+}
+
 // ---------- Stack ----------
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Stack<T> {
     lista: Vec<T>,
 }
@@ -59,8 +97,28 @@ impl<T> Stack<T> {
     }
 }
 
+impl<T> Default for Stack<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 // ---------- Tokenizacao ----------
 
+// posicao no codigo fonte, linha e coluna comecam em 1
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pos {
+    pub arquivo: Rc<str>,
+    pub linha: usize,
+    pub coluna: usize,
+}
+
+impl fmt::Display for Pos {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.arquivo, self.linha, self.coluna)
+    }
+}
+
 #[derive(Debug)]
 pub enum Token {
     // operadores
@@ -72,31 +130,87 @@ pub enum Token {
     Mais,
     Menor,
     Maior,
+    // efeito na stack: ( a b -- c:numero )
+    Separador,
+    DoisPontos,
     // numeros e nomes
     // keywords sao reconhecidas na criacao da ast mas queria ter feito aq
     Numero(i32),
     Simbolo(String),
     String(String),
+    // texto depois do #, so sai do tokenizar_com_comentarios
+    Comentario(String),
+}
+
+// para no primeiro erro
+fn tokenizar(entrada: &str, arquivo: &str) -> Result<Vec<(Token, Pos)>, Diagnostico> {
+    let (tokens, erros) = tokenizar_tudo(entrada, arquivo);
+    match erros.into_iter().next() {
+        Some(erro) => Err(erro),
+        None => Ok(tokens),
+    }
+}
+
+// o traco so fica entre letras ou numeros, como em assert-eq
+fn fechar_simbolo(nome: &str, pos: Pos) -> Result<(Token, Pos), Diagnostico> {
+    if nome.ends_with('-') || nome.contains("--") {
+        return Err(erro_sintaxe!(
+            pos,
+            "`{}`: o `-` num nome so pode ficar entre letras ou numeros",
+            nome
+        ));
+    }
+    Ok((Token::Simbolo(nome.to_owned()), pos))
 }
 
-fn tokenizar(entrada: &str) -> Vec<Token> {
+// junta todos os erros e continua depois de cada um
+fn tokenizar_tudo(entrada: &str, arquivo: &str) -> (Vec<(Token, Pos)>, Vec<Diagnostico>) {
+    let (mut tokens, erros) = tokenizar_com_comentarios(entrada, arquivo);
+    tokens.retain(|(token, _)| !matches!(token, Token::Comentario(_)));
+    (tokens, erros)
+}
+
+// sem perder nada do arquivo, pro formatador
+fn tokenizar_com_comentarios(
+    entrada: &str,
+    arquivo: &str,
+) -> (Vec<(Token, Pos)>, Vec<Diagnostico>) {
     let mut tokens = vec![];
+    let mut erros = vec![];
+
+    let arquivo: Rc<str> = Rc::from(arquivo);
+    let pos = |linha, coluna| Pos {
+        arquivo: arquivo.clone(),
+        linha,
+        coluna,
+    };
+    let (mut linha, mut coluna) = (1, 0);
+    let mut inicio = (1, 1);
 
     let mut buffer = String::new();
     #[derive(PartialEq)]
+    #[allow(clippy::upper_case_acronyms)]
     enum OQUE {
         Nada,
         Numero,
         Simbolo,
         String,
+        Traco,
+        Comentario,
     }
     let mut oqe = OQUE::Nada;
 
     for c in entrada.chars() {
         // log_info!("char: |{}|", c);
+        coluna += 1;
+        let aqui = (linha, coluna);
+        if c == '\n' {
+            linha += 1;
+            coluna = 0;
+        }
         if oqe == OQUE::Numero {
             match c {
-                c if c.is_digit(10) => {
+                c if c.is_ascii_digit() => {
                     buffer.push(c);
                     continue;
                 }
@@ -104,10 +218,14 @@ fn tokenizar(entrada: &str) -> Vec<Token> {
                     // log_error!("nao pode letra dps de numero: {:?}", c);
                 }
                 _ => {
-                    tokens.push(Token::Numero(match buffer.parse() {
-                        Ok(n) => n,
-                        Err(err) => log_error!("erro no parse de numero {}", err),
-                    }));
+                    match buffer.parse() {
+                        Ok(n) => tokens.push((Token::Numero(n), pos(inicio.0, inicio.1))),
+                        Err(err) => erros.push(erro_sintaxe!(
+                            pos(inicio.0, inicio.1),
+                            "erro no parse de numero {}",
+                            err
+                        )),
+                    }
                     // log_info!("terminou numero: |{}|", buffer);
                     buffer.clear();
                     oqe = OQUE::Nada;
@@ -115,20 +233,45 @@ fn tokenizar(entrada: &str) -> Vec<Token> {
             }
         } else if oqe == OQUE::Simbolo {
             match c {
-                c if (c.is_alphabetic() | c.is_digit(10)) => {
+                c if (c.is_alphabetic() | c.is_ascii_digit() | (c == '-')) => {
                     buffer.push(c);
                     continue;
                 }
                 _ => {
-                    tokens.push(Token::Simbolo(String::from(&buffer)));
+                    match fechar_simbolo(&buffer, pos(inicio.0, inicio.1)) {
+                        Ok(token) => tokens.push(token),
+                        Err(erro) => erros.push(erro),
+                    }
                     // log_info!("terminou simbolo: |{}|", buffer);
                     buffer.clear();
                     oqe = OQUE::Nada;
                 }
             }
+        } else if oqe == OQUE::Comentario {
+            if c == '\n' {
+                tokens.push((
+                    Token::Comentario(String::from(&buffer)),
+                    pos(inicio.0, inicio.1),
+                ));
+                buffer.clear();
+                oqe = OQUE::Nada;
+            } else {
+                buffer.push(c);
+            }
+            continue;
+        } else if oqe == OQUE::Traco {
+            oqe = OQUE::Nada;
+            if c == '-' {
+                tokens.push((Token::Separador, pos(inicio.0, inicio.1)));
+                continue;
+            }
+            erros.push(erro_sintaxe!(pos(inicio.0, inicio.1), "esperava `--`"));
         } else if oqe == OQUE::String {
             if c == '"' {
-                tokens.push(Token::String(String::from(&buffer)));
+                tokens.push((
+                    Token::String(String::from(&buffer)),
+                    pos(inicio.0, inicio.1),
+                ));
                 // log_info!("terminou string: |{}|", buffer);
                 buffer.clear();
                 oqe = OQUE::Nada;
@@ -138,47 +281,84 @@ fn tokenizar(entrada: &str) -> Vec<Token> {
             continue;
         }
         match c {
-            '=' => tokens.push(Token::Igual),
-            '(' => tokens.push(Token::ParenAbr),
-            ')' => tokens.push(Token::ParenFec),
-            '?' => tokens.push(Token::Interrogacao),
-            '!' => tokens.push(Token::Exclamacao),
-            '+' => tokens.push(Token::Mais),
-            '>' => tokens.push(Token::Maior),
-            '<' => tokens.push(Token::Menor),
+            '=' => tokens.push((Token::Igual, pos(aqui.0, aqui.1))),
+            '(' => tokens.push((Token::ParenAbr, pos(aqui.0, aqui.1))),
+            ')' => tokens.push((Token::ParenFec, pos(aqui.0, aqui.1))),
+            '?' => tokens.push((Token::Interrogacao, pos(aqui.0, aqui.1))),
+            '!' => tokens.push((Token::Exclamacao, pos(aqui.0, aqui.1))),
+            '+' => tokens.push((Token::Mais, pos(aqui.0, aqui.1))),
+            '>' => tokens.push((Token::Maior, pos(aqui.0, aqui.1))),
+            '<' => tokens.push((Token::Menor, pos(aqui.0, aqui.1))),
+            ':' => tokens.push((Token::DoisPontos, pos(aqui.0, aqui.1))),
+            '-' => {
+                inicio = aqui;
+                oqe = OQUE::Traco;
+            }
+            // comentario ate o fim da linha
+            '#' => {
+                inicio = aqui;
+                oqe = OQUE::Comentario;
+            }
             '"' => {
                 // log_info!("comecou string");
+                inicio = aqui;
                 oqe = OQUE::String;
             }
-            c if c.is_digit(10) => {
+            c if c.is_ascii_digit() => {
                 // log_info!("comecou numero");
+                inicio = aqui;
                 buffer.push(c);
                 oqe = OQUE::Numero;
             }
             c if c.is_alphabetic() => {
                 // log_info!("comecou simbolo");
+                if oqe == OQUE::Nada {
+                    inicio = aqui;
+                }
                 buffer.push(c);
                 oqe = OQUE::Simbolo;
             }
             c if c.is_whitespace() => (),
             outro => {
-                log_error!("nao sei: {:?}", outro);
+                erros.push(erro_sintaxe!(pos(aqui.0, aqui.1), "nao sei: {:?}", outro));
             }
         }
     }
 
     if !buffer.is_empty() {
         if oqe == OQUE::Numero {
-            tokens.push(Token::Numero(match buffer.parse() {
-                Ok(n) => n,
-                Err(err) => log_error!("nao deveria dar errado: {}", err),
-            }));
+            match buffer.parse() {
+                Ok(n) => tokens.push((Token::Numero(n), pos(inicio.0, inicio.1))),
+                Err(err) => erros.push(erro_sintaxe!(
+                    pos(inicio.0, inicio.1),
+                    "erro no parse de numero {}",
+                    err
+                )),
+            }
         } else if oqe == OQUE::Simbolo {
-            tokens.push(Token::Simbolo(String::from(&buffer)));
+            match fechar_simbolo(&buffer, pos(inicio.0, inicio.1)) {
+                Ok(token) => tokens.push(token),
+                Err(erro) => erros.push(erro),
+            }
         }
     }
-    // log_info!("tokens: {:?}", tokens);
-    tokens
+    if oqe == OQUE::Traco {
+        erros.push(erro_sintaxe!(pos(inicio.0, inicio.1), "esperava `--`"));
+    }
+    if oqe == OQUE::Comentario {
+        tokens.push((
+            Token::Comentario(String::from(&buffer)),
+            pos(inicio.0, inicio.1),
+        ));
+    }
+    if oqe == OQUE::String {
+        erros.push(erro_sintaxe!(
+            pos(inicio.0, inicio.1),
+            "string nao terminada: \"{}",
+            buffer
+        ));
+    }
+    (tokens, erros)
 }
 
 // ---------- AST ----------
@@ -207,40 +387,323 @@ pub enum ASTItem {
     SSize,
     If,
     DebugS,
+    // parada do --debug, fora dele nao faz nada
+    Break,
+    // closures
+    Curry,
+    Curry2,
+    Compose,
+    With,
+    // citacoes como dados
+    ToList,
+    ToQuot,
+    Size,
+    First,
+    Rest,
+    Append,
+    Word,
+    // metaprogramacao
+    Eval,
+    Define,
+    // excecoes
+    Throw,
+    Try,
+    Error,
+    ErrKind,
+    ErrMsg,
+    ErrPos,
+    // geradores
+    Gen,
+    Yield,
+    Next,
+    Take,
+    Map,
+    Filter,
+    Each,
+    // continuacoes
+    CallCC,
+    CallCCS,
+    // tarefas
+    Spawn,
+    Chan,
+    Send,
+    Recv,
+    // testes
+    Assert,
+    AssertEq,
+    AssertStack,
     // literais
     True,
     False,
     Numero(i32),
+    // valor capturado por curry/with, empilhado como esta
+    Valor(Item),
     // funcoes
     String(String),
     FuncDef(Func),
     FuncCallNamed(String),
     FuncCallTop,
+    // internos, so aparecem na stack de consumo
+    // fim do corpo de um try: tratador e tamanho da stack quando o try comecou
+    FimTry(Func, usize),
+    // fim do corpo de uma funcao nomeada, usado pra montar a cadeia de chamadas
+    FimChamada(Quadro),
+    // proxima iteracao de um each sobre gerador
+    EachGerador(Rc<RefCell<Gerador>>, Func),
+}
+
+#[derive(Debug, Clone)]
+pub struct Quadro {
+    pub nome: String,
+    // onde a funcao foi chamada
+    pub pos: Option<Rc<Pos>>,
+    // chamadas de cauda que reaproveitaram esse quadro
+    pub caudas: usize,
+    // com --check-effects: altura da stack sem as entradas, pra conferir no retorno
+    pub base: Option<usize>,
+}
+
+impl fmt::Display for Quadro {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.nome)?;
+        if self.caudas > 0 {
+            write!(f, " (+{} em cauda)", self.caudas)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct No {
+    pub item: ASTItem,
+    // None pra itens criados em tempo de execucao; compartilhada, copiar o no
+    // nao copia a posicao
+    pub pos: Option<Rc<Pos>>,
+}
+
+impl No {
+    pub fn new(item: ASTItem, pos: Pos) -> No {
+        No {
+            item,
+            pos: Some(Rc::new(pos)),
+        }
+    }
+
+    pub fn sem_pos(item: ASTItem) -> No {
+        No { item, pos: None }
+    }
+}
+
+type Func = Vec<No>;
+
+#[derive(Debug, Clone)]
+pub struct Definicao {
+    pub nome: String,
+    pub pos: Pos,
+    pub efeito: Option<Efeito>,
+    pub corpo: Func,
+}
+
+// efeito declarado na stack: nome ( a b:numero -- c ) = ( ... )
+#[derive(Debug, Clone)]
+pub struct Efeito {
+    pub entradas: Vec<Parametro>,
+    pub saidas: Vec<Parametro>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Parametro {
+    pub nome: String,
+    // um dos nomes de Item::tipo, None aceita qualquer coisa
+    pub tipo: Option<String>,
+}
+
+const TIPOS: &[&str] = &[
+    "bool",
+    "numero",
+    "string",
+    "funcao",
+    "lista",
+    "palavra",
+    "erro",
+    "gerador",
+    "continuacao",
+    "canal",
+];
+
+impl fmt::Display for Parametro {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.tipo {
+            Some(tipo) => write!(f, "{}:{}", self.nome, tipo),
+            None => write!(f, "{}", self.nome),
+        }
+    }
+}
+
+impl fmt::Display for Efeito {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "(")?;
+        for p in &self.entradas {
+            write!(f, " {}", p)?;
+        }
+        write!(f, " --")?;
+        for p in &self.saidas {
+            write!(f, " {}", p)?;
+        }
+        write!(f, " )")
+    }
+}
+
+// depois do nome da funcao, ate o parenteses que fecha o efeito
+fn gerar_efeito(tokens: &[(Token, Pos)], i: &mut usize) -> Result<Efeito, Diagnostico> {
+    let mut entradas = vec![];
+    let mut saidas = vec![];
+    let mut depois_separador = false;
+    loop {
+        let Some((token, pos)) = tokens.get(*i) else {
+            return Err(erro_sintaxe!(
+                tokens[*i - 1].1,
+                "efeito sem parenteses no final"
+            ));
+        };
+        *i += 1;
+        match token {
+            Token::ParenFec => break,
+            Token::Separador if !depois_separador => depois_separador = true,
+            Token::Simbolo(nome) => {
+                let mut tipo = None;
+                if let Some((Token::DoisPontos, _)) = tokens.get(*i) {
+                    let Some((Token::Simbolo(t), pos_tipo)) = tokens.get(*i + 1) else {
+                        return Err(erro_sintaxe!(pos, "falta o tipo depois do `:`"));
+                    };
+                    if !TIPOS.contains(&t.as_str()) {
+                        return Err(erro_sintaxe!(
+                            pos_tipo,
+                            "tipo `{}` nao existe, os tipos sao: {}",
+                            t,
+                            TIPOS.join(" ")
+                        ));
+                    }
+                    tipo = Some(t.clone());
+                    *i += 2;
+                }
+                let parametro = Parametro {
+                    nome: nome.clone(),
+                    tipo,
+                };
+                if depois_separador {
+                    saidas.push(parametro);
+                } else {
+                    entradas.push(parametro);
+                }
+            }
+            _ => {
+                return Err(erro_sintaxe!(
+                    pos,
+                    "token inesperado no efeito: {:?}",
+                    token
+                ));
+            }
+        }
+    }
+    if !depois_separador {
+        return Err(erro_sintaxe!(tokens[*i - 1].1, "efeito sem `--`"));
+    }
+    Ok(Efeito { entradas, saidas })
 }
 
-type Func = Vec<ASTItem>;
+#[allow(clippy::upper_case_acronyms)]
+type AST = Vec<Definicao>;
+
+// palavras reservadas, tambem usado pra transformar listas de volta em citacoes
+const PALAVRAS: &[(&str, ASTItem)] = &[
+    ("+", ASTItem::Mais),
+    (">", ASTItem::Maior),
+    ("<", ASTItem::Menor),
+    ("!", ASTItem::FuncCallTop),
+    ("print", ASTItem::Print),
+    ("input", ASTItem::Input),
+    ("pop", ASTItem::Pop),
+    ("dup", ASTItem::Dup),
+    ("swap", ASTItem::Swap),
+    ("swapn", ASTItem::SwapN),
+    ("ssize", ASTItem::SSize),
+    ("if", ASTItem::If),
+    ("debugs", ASTItem::DebugS),
+    ("break", ASTItem::Break),
+    ("curry", ASTItem::Curry),
+    ("curry2", ASTItem::Curry2),
+    ("compose", ASTItem::Compose),
+    ("with", ASTItem::With),
+    ("tolist", ASTItem::ToList),
+    ("toquot", ASTItem::ToQuot),
+    ("size", ASTItem::Size),
+    ("first", ASTItem::First),
+    ("rest", ASTItem::Rest),
+    ("append", ASTItem::Append),
+    ("word", ASTItem::Word),
+    ("eval", ASTItem::Eval),
+    ("define", ASTItem::Define),
+    ("throw", ASTItem::Throw),
+    ("try", ASTItem::Try),
+    ("error", ASTItem::Error),
+    ("errkind", ASTItem::ErrKind),
+    ("errmsg", ASTItem::ErrMsg),
+    ("errpos", ASTItem::ErrPos),
+    ("gen", ASTItem::Gen),
+    ("yield", ASTItem::Yield),
+    ("next", ASTItem::Next),
+    ("take", ASTItem::Take),
+    ("map", ASTItem::Map),
+    ("filter", ASTItem::Filter),
+    ("each", ASTItem::Each),
+    ("callcc", ASTItem::CallCC),
+    ("callccs", ASTItem::CallCCS),
+    ("spawn", ASTItem::Spawn),
+    ("chan", ASTItem::Chan),
+    ("send", ASTItem::Send),
+    ("recv", ASTItem::Recv),
+    ("assert", ASTItem::Assert),
+    ("assert-eq", ASTItem::AssertEq),
+    ("assert-stack", ASTItem::AssertStack),
+    ("true", ASTItem::True),
+    ("false", ASTItem::False),
+];
+
+fn palavra_builtin(nome: &str) -> Option<ASTItem> {
+    PALAVRAS
+        .iter()
+        .find(|(n, _)| *n == nome)
+        .map(|(_, item)| item.clone())
+}
 
-type AST = Vec<(String, Func)>;
+// inverso do palavra_builtin, None pra literais e chamadas nomeadas
+fn nome_builtin(item: &ASTItem) -> Option<&'static str> {
+    let d = std::mem::discriminant(item);
+    PALAVRAS
+        .iter()
+        .find(|(_, i)| std::mem::discriminant(i) == d)
+        .map(|(n, _)| *n)
+}
 
-fn gerar_ast_funcao(tokens: &Vec<Token>, i: &mut usize) -> Func {
+fn gerar_ast_funcao(tokens: &[(Token, Pos)], i: &mut usize) -> Result<Func, Diagnostico> {
     let mut funcao_atual: Func = vec![];
-    let mut stack_funcoes: Stack<Func> = Stack::new();
-    let mut criando_funcao = false;
+    let mut stack_funcoes: Stack<(Func, Pos)> = Stack::new();
+    let mut criando_funcao: Option<Pos> = None;
 
     loop {
         if *i == tokens.len() {
             break;
         }
-        let atual = &tokens[*i];
+        let (atual, pos) = &tokens[*i];
         *i += 1;
         match atual {
             Token::ParenAbr => {
-                if criando_funcao {
-                    criando_funcao = false;
-                    stack_funcoes.push(funcao_atual.to_vec());
+                if let Some(inicio) = criando_funcao.take() {
+                    stack_funcoes.push((funcao_atual.to_vec(), inicio));
                     funcao_atual.clear();
                 } else {
-                    log_error!("parenteses sem ter interrogacao antes");
+                    return Err(erro_sintaxe!(pos, "parenteses sem ter interrogacao antes"));
                 }
             }
             Token::ParenFec => {
@@ -249,387 +712,2130 @@ fn gerar_ast_funcao(tokens: &Vec<Token>, i: &mut usize) -> Func {
                     break;
                 } else {
                     let f = funcao_atual.to_vec();
-                    funcao_atual = match stack_funcoes.pop() {
+                    let inicio;
+                    (funcao_atual, inicio) = match stack_funcoes.pop() {
                         Some(f) => f,
-                        None => {
-                            log_error!("erro no parenfec");
-                        }
+                        None => return Err(erro_sintaxe!(pos, "erro no parenfec")),
                     };
-                    funcao_atual.push(ASTItem::FuncDef(f));
+                    funcao_atual.push(No::new(ASTItem::FuncDef(f), inicio));
                 }
             }
             Token::Interrogacao => {
-                criando_funcao = true;
+                criando_funcao = Some(pos.clone());
             }
             Token::Exclamacao => {
-                funcao_atual.push(ASTItem::FuncCallTop);
+                funcao_atual.push(No::new(ASTItem::FuncCallTop, pos.clone()));
             }
             Token::Mais => {
-                funcao_atual.push(ASTItem::Mais);
+                funcao_atual.push(No::new(ASTItem::Mais, pos.clone()));
             }
             Token::Maior => {
-                funcao_atual.push(ASTItem::Maior);
+                funcao_atual.push(No::new(ASTItem::Maior, pos.clone()));
             }
             Token::Menor => {
-                funcao_atual.push(ASTItem::Menor);
+                funcao_atual.push(No::new(ASTItem::Menor, pos.clone()));
             }
             Token::Numero(n) => {
-                funcao_atual.push(ASTItem::Numero(*n));
-            }
-            Token::Simbolo(nome) => match nome.as_str() {
-                "print" => funcao_atual.push(ASTItem::Print),
-                "input" => funcao_atual.push(ASTItem::Input),
-                "pop" => funcao_atual.push(ASTItem::Pop),
-                "dup" => funcao_atual.push(ASTItem::Dup),
-                "swap" => funcao_atual.push(ASTItem::Swap),
-                "swapn" => funcao_atual.push(ASTItem::SwapN),
-                "ssize" => funcao_atual.push(ASTItem::SSize),
-                "if" => funcao_atual.push(ASTItem::If),
-                "debugs" => funcao_atual.push(ASTItem::DebugS),
-                "true" => funcao_atual.push(ASTItem::True),
-                "false" => funcao_atual.push(ASTItem::False),
-                _ => funcao_atual.push(ASTItem::FuncCallNamed(nome.to_string())),
-            },
+                funcao_atual.push(No::new(ASTItem::Numero(*n), pos.clone()));
+            }
+            Token::Simbolo(nome) => {
+                let item = match palavra_builtin(nome) {
+                    Some(item) => item,
+                    None => ASTItem::FuncCallNamed(nome.to_string()),
+                };
+                funcao_atual.push(No::new(item, pos.clone()));
+            }
             Token::String(s) => {
-                funcao_atual.push(ASTItem::String(s.clone()));
+                funcao_atual.push(No::new(ASTItem::String(s.clone()), pos.clone()));
             }
             Token::Igual => {
-                log_error!("impossível ter igual dentro de uma funcao");
+                return Err(erro_sintaxe!(
+                    pos,
+                    "impossível ter igual dentro de uma funcao"
+                ));
+            }
+            // tirados pelo tokenizar_tudo
+            Token::Comentario(_) => {}
+            Token::Separador | Token::DoisPontos => {
+                return Err(erro_sintaxe!(
+                    pos,
+                    "`--` e `:` so podem aparecer no efeito de uma funcao"
+                ));
             }
         }
     }
 
-    funcao_atual
+    if let Some(pos) = criando_funcao {
+        return Err(erro_sintaxe!(pos, "interrogacao sem parenteses depois"));
+    }
+    if let Some((_, pos)) = stack_funcoes.pop() {
+        return Err(erro_sintaxe!(pos, "parenteses nao fechado"));
+    }
+
+    Ok(funcao_atual)
+}
+
+// para no primeiro erro
+fn gerar_ast(tokens: Vec<(Token, Pos)>, funcao: bool) -> Result<AST, Diagnostico> {
+    let (ast, erros) = gerar_ast_tudo(&tokens, funcao);
+    match erros.into_iter().next() {
+        Some(erro) => Err(erro),
+        None => Ok(ast),
+    }
 }
 
-fn gerar_ast(tokens: Vec<Token>, funcao: bool) -> AST {
+// depois de um erro pula pra proxima definicao e continua
+fn gerar_ast_tudo(tokens: &[(Token, Pos)], funcao: bool) -> (AST, Vec<Diagnostico>) {
     let mut ast = vec![];
+    let mut erros = vec![];
     let mut i: usize = 0;
 
     if funcao {
-        ast.push((String::from("funcao"), gerar_ast_funcao(&tokens, &mut i)));
+        let pos = match tokens.first() {
+            Some((_, pos)) => pos.clone(),
+            None => Pos {
+                arquivo: Rc::from(""),
+                linha: 1,
+                coluna: 1,
+            },
+        };
+        match gerar_ast_funcao(tokens, &mut i) {
+            Ok(corpo) => ast.push(Definicao {
+                nome: String::from("funcao"),
+                pos,
+                efeito: None,
+                corpo,
+            }),
+            Err(erro) => erros.push(erro),
+        }
+        if let (true, Some((_, pos))) = (erros.is_empty(), tokens.get(i)) {
+            erros.push(erro_sintaxe!(pos, "parenteses fechando sem abrir"));
+        }
     } else {
-        loop {
-            if i == tokens.len() {
-                break;
+        while i < tokens.len() {
+            let inicio = i;
+            match gerar_definicao(tokens, &mut i) {
+                Ok(definicao) => ast.push(definicao),
+                Err(erro) => {
+                    erros.push(erro);
+                    i = (inicio + 1..tokens.len())
+                        .find(|&j| comeco_definicao(tokens, j))
+                        .unwrap_or(tokens.len());
+                }
             }
-            let Token::Simbolo(nome) = &tokens[i] else {
-                log_error!("falta nome no começo de uma funcao");
-            };
-            i += 1;
-            let Token::Igual = &tokens[i] else {
-                log_error!("falta um igual na definicao da funcao {}", nome);
-            };
-            i += 1;
-            let Token::ParenAbr = &tokens[i] else {
-                log_error!("falta um parenteses no comeco da funcao {}", nome);
-            };
-            i += 1;
-            let func = gerar_ast_funcao(&tokens, &mut i);
-            let Token::ParenFec = &tokens[i] else {
-                log_error!("falta um parenteses no final da funcao {}", nome);
-            };
-            i += 1;
-            ast.push((nome.to_string(), func));
         }
     }
 
-    // println!("ast: {ast:?}");
-
-    ast
+    (ast, erros)
 }
 
-// ---------- Interpretacao ----------
-
-#[derive(Debug, Clone)]
-#[allow(unused)]
-pub enum Item {
-    Bool(bool),
-    Numero(i32),
-    String(String),
-    Func(Vec<ASTItem>),
+// um nome no comeco da linha seguido de igual ou de efeito
+fn comeco_definicao(tokens: &[(Token, Pos)], i: usize) -> bool {
+    matches!(
+        (&tokens[i], tokens.get(i + 1)),
+        (
+            (Token::Simbolo(_), Pos { coluna: 1, .. }),
+            Some((Token::Igual | Token::ParenAbr, _))
+        )
+    )
 }
 
-pub fn interpretar_func(estado: &mut PSFState, func: Func) {
-    let mut stack_consumir = Stack::new();
-    for item in func.iter().rev() {
-        stack_consumir.push(item.clone());
+fn gerar_definicao(tokens: &[(Token, Pos)], i: &mut usize) -> Result<Definicao, Diagnostico> {
+    let (Token::Simbolo(nome), pos) = &tokens[*i] else {
+        return Err(erro_sintaxe!(
+            tokens[*i].1,
+            "falta nome no começo de uma funcao"
+        ));
+    };
+    *i += 1;
+    let mut efeito = None;
+    if let Some((Token::ParenAbr, _)) = tokens.get(*i) {
+        *i += 1;
+        efeito = Some(gerar_efeito(tokens, i)?);
     }
-    let mut item: ASTItem;
-    loop {
-        // log_info!(
-        //     "\nstack: {:?}\ncons: {:?}",
-        //     estado.stack.lista,
-        //     stack_consumir.lista
-        // );
-        if estado.stack.len() > 1000 {
-            log_error!("stack muito grande, terminando programa");
-        }
-        item = match stack_consumir.pop() {
-            Some(i) => i,
-            None => break,
-        };
-        match item {
-            ASTItem::True => {
-                estado.stack.push(Item::Bool(true));
-            }
-            ASTItem::False => {
-                estado.stack.push(Item::Bool(false));
-            }
-            ASTItem::Numero(n) => {
-                estado.stack.push(Item::Numero(n));
-            }
-            ASTItem::String(s) => {
-                estado.stack.push(Item::String(s));
-            }
-            ASTItem::Mais => {
-                let Some(Item::Numero(n1)) = estado.stack.pop() else {
-                    log_error!("tipo do primeiro argumento nao e numero");
-                };
-                let Some(Item::Numero(n2)) = estado.stack.pop() else {
-                    log_error!("tipo do segundo argumento nao e numero");
-                };
-                estado.stack.push(Item::Numero(n1 + n2));
-            }
-            ASTItem::Maior => {
-                let Some(Item::Numero(n1)) = estado.stack.pop() else {
-                    log_error!("tipo do primeiro argumento nao e numero");
-                };
-                let Some(Item::Numero(n2)) = estado.stack.pop() else {
-                    log_error!("tipo do segundo argumento nao e numero");
-                };
-                estado.stack.push(Item::Numero(n2));
-                estado.stack.push(Item::Numero(n1));
-                estado.stack.push(Item::Bool(n2 > n1));
-            }
-            ASTItem::Menor => {
-                let Some(Item::Numero(n1)) = estado.stack.pop() else {
-                    log_error!("tipo do primeiro argumento nao e numero");
-                };
-                let Some(Item::Numero(n2)) = estado.stack.pop() else {
-                    log_error!("tipo do segundo argumento nao e numero");
-                };
-                estado.stack.push(Item::Numero(n2));
-                estado.stack.push(Item::Numero(n1));
-                estado.stack.push(Item::Bool(n2 < n1));
-            }
-            ASTItem::Print => {
-                let Some(item) = estado.stack.pop() else {
-                    log_error!("stack vazia antes do print");
-                };
-                estado.stack.push(item.clone());
-                // log_info!("item do print: {:?}", item);
-                match item {
-                    Item::Numero(n) => println!("{}", n),
-                    Item::Bool(b) => println!("{}", b),
-                    Item::String(s) => println!("{}", s),
-                    outro => {
-                        println!("impossivel: {:?}", outro);
-                    }
-                }
-            }
-            ASTItem::Input => {
-                let Some(item) = estado.stack.pop() else {
-                    log_error!("stack vaiz antes do input");
-                };
-                let Item::String(s) = item else {
-                    log_error!("input so aceita string como entrada");
-                };
-                print!("{}", s);
-                let mut input = String::new();
-                _ = io::stdout().flush();
-                io::stdin().read_line(&mut input).unwrap();
-                estado.stack.push(Item::String(input));
-            }
-            ASTItem::Pop => {
-                _ = estado.stack.pop();
-            }
-            ASTItem::Dup => {
-                let Some(topo) = estado.stack.pop() else {
-                    log_error!("stack vazia na chamada do dup");
-                };
-                estado.stack.push(topo.clone());
-                estado.stack.push(topo);
-            }
-            ASTItem::Swap => {
-                let Some(prim) = estado.stack.pop() else {
-                    log_error!("stack vazia na chamada do swap");
-                };
-                let Some(seg) = estado.stack.pop() else {
-                    log_error!("stack com somente um elemento na chamada do swap");
-                };
-                estado.stack.push(prim);
-                estado.stack.push(seg);
-            }
-            ASTItem::SwapN => {
-                let Some(posi) = estado.stack.pop() else {
-                    log_error!("stack vazia na chamada do swapn");
-                };
-                let Item::Numero(pos) = posi else {
-                    log_error!("topo da stack nao e numero");
-                };
-                if pos < 0 {
-                    log_error!("impossivel trocar posicao negativa: {}", pos);
-                }
-                let tam = estado.stack.len() as i32;
-                if pos >= tam {
-                    log_error!(
-                        "tentando indexar {} mas a stack ta com tamanho {}",
-                        pos,
-                        tam
-                    );
-                } else if pos == 0 {
-                    return;
-                }
-                let topi = estado.stack.lista[tam as usize - 1].clone();
-                let nth = estado.stack.lista[tam as usize - 1 - pos as usize].clone();
-                estado.stack.lista[tam as usize - 1] = nth;
-                estado.stack.lista[tam as usize - 1 - pos as usize] = topi;
-            }
-            ASTItem::SSize => {
-                estado.stack.push(Item::Numero(estado.stack.len() as i32));
-            }
-            ASTItem::If => {
-                let Some(iff) = estado.stack.pop() else {
-                    log_error!("stack vazia na chamada do if");
-                };
-                let Some(ifv) = estado.stack.pop() else {
-                    log_error!("stack com somente um elemento no if");
-                };
-                let Some(ibo) = estado.stack.pop() else {
-                    log_error!("stack sem terceiro item no if");
-                };
-                let Item::Func(ff) = iff else {
-                    log_error!("funcao do else nao e funcao");
-                };
-                let Item::Func(fv) = ifv else {
-                    log_error!("funcao do if nao e funcao");
-                };
-                let Item::Bool(bo) = ibo else {
-                    log_error!("valor do if nao e booleano");
-                };
-                if bo {
-                    estado.stack.push(Item::Func(fv));
-                } else {
-                    estado.stack.push(Item::Func(ff));
-                }
-                stack_consumir.push(ASTItem::FuncCallTop);
-            }
-            ASTItem::DebugS => {
-                println!("debug: {:?}", estado.stack);
-            }
-            ASTItem::FuncDef(f) => {
-                estado.stack.push(Item::Func(f));
-            }
-            ASTItem::FuncCallTop => {
-                let Some(f) = estado.stack.pop() else {
-                    log_error!("tentativa de chamar topo da funcao mas nao tem item");
-                };
-                let Item::Func(f) = f else {
-                    log_error!("topo da funcao nao é funcao");
-                };
-                for i in f.iter().rev() {
-                    stack_consumir.push(i.clone());
-                }
-            }
-            ASTItem::FuncCallNamed(f) => {
-                let Some(f) = estado.funcoes.get(&f) else {
-                    log_error!("funcao `{:?}` nao existe", f);
-                };
-                for i in f.iter().rev() {
-                    stack_consumir.push(i.clone());
-                }
-            }
+    let Some((Token::Igual, _)) = tokens.get(*i) else {
+        return Err(erro_sintaxe!(
+            tokens[*i - 1].1,
+            "falta um igual na definicao da funcao {}",
+            nome
+        ));
+    };
+    *i += 1;
+    let Some((Token::ParenAbr, _)) = tokens.get(*i) else {
+        return Err(erro_sintaxe!(
+            tokens[*i - 1].1,
+            "falta um parenteses no comeco da funcao {}",
+            nome
+        ));
+    };
+    *i += 1;
+    let corpo = gerar_ast_funcao(tokens, i)?;
+    let Some((Token::ParenFec, _)) = tokens.get(*i) else {
+        return Err(erro_sintaxe!(
+            tokens[*i - 1].1,
+            "falta um parenteses no final da funcao {}",
+            nome
+        ));
+    };
+    *i += 1;
+    Ok(Definicao {
+        nome: nome.to_string(),
+        pos: pos.clone(),
+        efeito,
+        corpo,
+    })
+}
+
+// chama f pra cada no da funcao, entrando nas citacoes
+fn visitar_nos<'a>(func: &'a Func, f: &mut impl FnMut(&'a No)) {
+    for no in func {
+        f(no);
+        if let ASTItem::FuncDef(corpo) = &no.item {
+            visitar_nos(corpo, f);
         }
     }
 }
 
-// ---------- Estado ----------
+// ---------- Resolucao ----------
 
-#[derive(Debug)]
-pub struct PSFState {
-    #[allow(dead_code, unused)]
-    stack: Stack<Item>,
-    #[allow(dead_code, unused)]
-    funcoes: HashMap<String, Func>,
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Nivel {
+    Erro,
+    Aviso,
 }
 
-impl PSFState {
-    pub fn new() -> PSFState {
-        PSFState {
-            stack: Stack::new(),
-            funcoes: HashMap::new(),
+#[derive(Debug, Clone)]
+pub struct Diagnostico {
+    pub nivel: Nivel,
+    pub mensagem: String,
+    pub pos: Option<Pos>,
+    pub sugestao: Option<String>,
+}
+
+impl Diagnostico {
+    pub fn erro(mensagem: String, pos: Option<Pos>) -> Diagnostico {
+        Diagnostico {
+            nivel: Nivel::Erro,
+            mensagem,
+            pos,
+            sugestao: None,
         }
     }
 
-    pub fn clear_stack(&mut self) {
-        self.stack.lista.clear();
+    pub fn aviso(mensagem: String, pos: Option<Pos>) -> Diagnostico {
+        Diagnostico {
+            nivel: Nivel::Aviso,
+            mensagem,
+            pos,
+            sugestao: None,
+        }
     }
 
-    #[allow(dead_code, unused)]
-    pub fn load_ast(&mut self, ast: AST) {
-        for (nome, funcao) in ast {
-            self.funcoes.insert(nome, funcao);
-        }
+    pub fn com_sugestao(mut self, sugestao: Option<String>) -> Diagnostico {
+        self.sugestao = sugestao;
+        self
     }
 
-    #[allow(dead_code, unused)]
-    pub fn load_string(&mut self, entrada: &str) {
-        self.load_ast(tokenizar_e_gerar_ast(entrada, false));
+    pub fn mostrar(&self) {
+        match self.nivel {
+            Nivel::Erro => {
+                print!("\x1b[1;31merro\x1b[0m: ");
+                println!("{}", self);
+            }
+            Nivel::Aviso => {
+                log_aviso!("{}", self);
+            }
+        }
+        if let Some(sugestao) = &self.sugestao {
+            println!("    ajuda: {}", sugestao);
+        }
     }
+}
 
-    #[allow(dead_code, unused)]
-    pub fn run_raw_string(&mut self, entrada: &str) {
-        let itens = tokenizar_e_gerar_ast(entrada, true);
-        let (_, funcao) = itens.get(0).unwrap();
-        interpretar_func(self, funcao.to_vec());
+impl fmt::Display for Diagnostico {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.pos {
+            Some(pos) => write!(f, "{}: {}", pos, self.mensagem),
+            None => write!(f, "{}", self.mensagem),
+        }
     }
+}
 
-    pub fn run_function(&mut self, f: &str) {
-        interpretar_func(
-            self,
-            self.funcoes.get(f).expect("funcao nao existe").to_vec(),
-        );
+// checa se toda chamada nomeada tem uma funcao definida ou carregada antes,
+// e avisa de definicoes repetidas ou com nome de palavra reservada
+pub fn resolver(ast: &AST, funcoes: &HashMap<String, Func>) -> Vec<Diagnostico> {
+    let mut diagnosticos = vec![];
+    let mut definidas: HashMap<&str, &Pos> = HashMap::new();
+    for definicao in ast {
+        let nome = definicao.nome.as_str();
+        if palavra_builtin(nome).is_some() {
+            diagnosticos.push(
+                Diagnostico::aviso(
+                    format!(
+                        "a funcao `{}` nunca e chamada, `{}` e uma palavra reservada",
+                        nome, nome
+                    ),
+                    Some(definicao.pos.clone()),
+                )
+                .com_sugestao(Some("troque o nome da funcao".to_owned())),
+            );
+        }
+        if let Some(anterior) = definidas.insert(nome, &definicao.pos) {
+            diagnosticos.push(Diagnostico::aviso(
+                format!(
+                    "funcao `{}` definida de novo, a definicao de {} e descartada",
+                    nome, anterior
+                ),
+                Some(definicao.pos.clone()),
+            ));
+        } else if funcoes.contains_key(nome) {
+            diagnosticos.push(Diagnostico::aviso(
+                format!("funcao `{}` ja existia e vai ser substituida", nome),
+                Some(definicao.pos.clone()),
+            ));
+        }
     }
 
-    pub fn run_main(&mut self) {
-        self.run_function("main");
+    // com eval ou define o nome pode aparecer so em tempo de execucao
+    let mut dinamico = false;
+    for definicao in ast {
+        visitar_nos(&definicao.corpo, &mut |no| {
+            dinamico |= matches!(no.item, ASTItem::Eval | ASTItem::Define);
+        });
     }
 
-    pub fn load_funcs(&mut self) {
-        self.funcoes
-            .insert("printp".to_owned(), vec![ASTItem::Print, ASTItem::Pop]);
+    let mut candidatos: Vec<&str> = PALAVRAS.iter().map(|(nome, _)| *nome).collect();
+    candidatos.extend(definidas.keys());
+    candidatos.extend(funcoes.keys().map(|nome| nome.as_str()));
+    for definicao in ast {
+        visitar_nos(&definicao.corpo, &mut |no| {
+            let ASTItem::FuncCallNamed(nome) = &no.item else {
+                return;
+            };
+            if definidas.contains_key(nome.as_str()) || funcoes.contains_key(nome) {
+                return;
+            }
+            let mensagem = format!("funcao `{}` nao existe", nome);
+            let diagnostico = if dinamico {
+                Diagnostico::aviso(
+                    format!("{} (pode ser criada com define ou eval)", mensagem),
+                    no.pos.as_deref().cloned(),
+                )
+            } else {
+                Diagnostico::erro(mensagem, no.pos.as_deref().cloned())
+            };
+            let sugestao =
+                sugerir_nome(nome, &candidatos).map(|s| format!("voce quis dizer `{}`?", s));
+            diagnosticos.push(diagnostico.com_sugestao(sugestao));
+        });
     }
+    diagnosticos
 }
 
-// ---------- Helpers ----------
-
-fn tokenizar_e_gerar_ast(entrada: &str, funcao: bool) -> AST {
-    gerar_ast(tokenizar(entrada), funcao)
+// candidato mais parecido, se for parecido o suficiente
+fn sugerir_nome<'a>(nome: &str, candidatos: &[&'a str]) -> Option<&'a str> {
+    let maximo = (nome.chars().count() / 3).max(1);
+    candidatos
+        .iter()
+        .map(|c| (distancia_edicao(nome, c), *c))
+        .filter(|(d, _)| *d <= maximo)
+        .min()
+        .map(|(_, c)| c)
 }
 
-fn print_usage() {
-    println!(
-        "[ARQUIVO | [OPCOES]*]
-OPCOES:
-    -h help
-    -i interativo"
-    );
+// distancia de levenshtein
+fn distancia_edicao(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut anterior: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut atual = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let troca = anterior[j] + usize::from(ca != *cb);
+            atual.push(troca.min(anterior[j + 1] + 1).min(atual[j] + 1));
+        }
+        anterior = atual;
+    }
+    anterior[b.len()]
 }
 
-fn print_usage_repl() {
-    println!(
-        "usagem
-    :h :help :?   print help
-    :e :exit      exit"
-    );
-}
+// ---------- Interpretacao ----------
+
+#[derive(Debug, Clone)]
+#[allow(unused)]
+pub enum Item {
+    Bool(bool),
+    Numero(i32),
+    String(String),
+    Func(Func),
+    Lista(Vec<Item>),
+    // nome de uma palavra dentro de uma citacao transformada em lista
+    Palavra(String),
+    Erro(Box<ErroPSF>),
+    // compartilhado: copias com dup avancam o mesmo gerador
+    Gerador(Rc<RefCell<Gerador>>),
+    Continuacao(Rc<Continuacao>),
+    Canal(Rc<Canal>),
+}
+
+// o resto da computacao no momento do callcc
+#[derive(Debug)]
+pub struct Continuacao {
+    stack_consumir: Stack<No>,
+    // so no callccs, restaurada junto quando a continuacao e chamada
+    stack: Option<Stack<Item>>,
+}
+
+impl Item {
+    pub fn tipo(&self) -> &'static str {
+        match self {
+            Item::Bool(_) => "bool",
+            Item::Numero(_) => "numero",
+            Item::String(_) => "string",
+            Item::Func(_) => "funcao",
+            Item::Lista(_) => "lista",
+            Item::Palavra(_) => "palavra",
+            Item::Erro(_) => "erro",
+            Item::Gerador(_) => "gerador",
+            Item::Continuacao(_) => "continuacao",
+            Item::Canal(_) => "canal",
+        }
+    }
+}
+
+impl fmt::Display for Item {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Item::Bool(b) => write!(f, "{}", b),
+            Item::Numero(n) => write!(f, "{}", n),
+            Item::String(s) => write!(f, "{}", s),
+            Item::Func(func) => write!(f, "?({})", formatar_func(func)),
+            Item::Lista(l) => write!(f, "[{}]", formatar_itens(l)),
+            Item::Palavra(p) => write!(f, "{}", p),
+            Item::Erro(e) => write!(f, "erro {}: {}", e.tipo, e),
+            Item::Gerador(_) => write!(f, "<gerador>"),
+            Item::Continuacao(_) => write!(f, "<continuacao>"),
+            Item::Canal(c) => write!(f, "<canal {}>", c.fila.borrow().len()),
+        }
+    }
+}
+
+// itens separados por espaco, strings entre aspas
+fn formatar_itens(itens: &[Item]) -> String {
+    itens
+        .iter()
+        .map(|item| match item {
+            Item::String(s) => format!("{:?}", s),
+            outro => outro.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn formatar_func(func: &Func) -> String {
+    func.iter()
+        .map(|no| match ast_para_item(&no.item) {
+            Item::String(s) => format!("{:?}", s),
+            outro => outro.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// geradores, continuacoes e canais so sao o mesmo se forem o mesmo objeto
+fn mesmo_item(a: &Item, b: &Item) -> bool {
+    match (a, b) {
+        (Item::Bool(a), Item::Bool(b)) => a == b,
+        (Item::Numero(a), Item::Numero(b)) => a == b,
+        (Item::String(a), Item::String(b)) | (Item::Palavra(a), Item::Palavra(b)) => a == b,
+        (Item::Lista(a), Item::Lista(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| mesmo_item(a, b))
+        }
+        (Item::Gerador(a), Item::Gerador(b)) => Rc::ptr_eq(a, b),
+        (Item::Continuacao(a), Item::Continuacao(b)) => Rc::ptr_eq(a, b),
+        (Item::Canal(a), Item::Canal(b)) => Rc::ptr_eq(a, b),
+        (a, b) => a.tipo() == b.tipo() && a.to_string() == b.to_string(),
+    }
+}
+
+// item da citacao -> valor manipulavel pelo programa
+fn ast_para_item(item: &ASTItem) -> Item {
+    match item {
+        ASTItem::True => Item::Bool(true),
+        ASTItem::False => Item::Bool(false),
+        ASTItem::Numero(n) => Item::Numero(*n),
+        ASTItem::String(s) => Item::String(s.clone()),
+        ASTItem::Valor(v) => v.clone(),
+        ASTItem::FuncDef(f) => Item::Func(f.clone()),
+        ASTItem::FuncCallNamed(nome) => Item::Palavra(nome.clone()),
+        outro => Item::Palavra(nome_builtin(outro).unwrap_or("?").to_owned()),
+    }
+}
+
+// valor -> item de citacao, palavras viram chamadas
+fn item_para_ast(item: Item) -> ASTItem {
+    match item {
+        Item::Bool(true) => ASTItem::True,
+        Item::Bool(false) => ASTItem::False,
+        Item::Numero(n) => ASTItem::Numero(n),
+        Item::String(s) => ASTItem::String(s),
+        Item::Func(f) => ASTItem::FuncDef(f),
+        Item::Palavra(p) => palavra_builtin(&p).unwrap_or(ASTItem::FuncCallNamed(p)),
+        outro => ASTItem::Valor(outro),
+    }
+}
+
+// erro em tempo de execucao, pode ser pego com try
+#[derive(Debug, Clone)]
+pub struct ErroPSF {
+    // stack-vazia, tipo, indice, aritmetica, nome, limite, usuario...
+    pub tipo: String,
+    pub mensagem: String,
+    pub pos: Option<Pos>,
+    // preenchido no passo onde o erro aconteceu
+    pub contexto: Option<Box<ContextoErro>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ContextoErro {
+    // funcoes nomeadas em execucao quando o erro aconteceu, a mais externa primeiro
+    pub rastro: Vec<Quadro>,
+    // topo da stack quando o erro aconteceu
+    pub stack: Vec<Item>,
+    pub tam_stack: usize,
+}
+
+// quantos itens do topo da stack entram no relatorio de erro
+const ITENS_RELATORIO: usize = 5;
+
+impl ErroPSF {
+    pub fn new(tipo: &str, mensagem: String) -> ErroPSF {
+        ErroPSF {
+            tipo: tipo.to_owned(),
+            mensagem,
+            pos: None,
+            contexto: None,
+        }
+    }
+
+    // mensagem com o rastro de chamadas e o topo da stack, pra erros que matam o programa
+    pub fn relatorio(&self) -> String {
+        let mut saida = self.to_string();
+        let Some(contexto) = &self.contexto else {
+            return saida;
+        };
+        if !contexto.rastro.is_empty() {
+            saida.push_str("\nrastro (mais recente por ultimo):");
+            for quadro in &contexto.rastro {
+                saida.push_str(&format!("\n    em {}", quadro));
+                if let Some(pos) = &quadro.pos {
+                    saida.push_str(&format!(", chamada em {}", pos));
+                }
+            }
+        }
+        saida.push_str(&format!("\nstack ({} itens", contexto.tam_stack));
+        if contexto.tam_stack > contexto.stack.len() {
+            saida.push_str(&format!(", ultimos {}", contexto.stack.len()));
+        }
+        saida.push_str(&format!("): {}", formatar_itens(&contexto.stack)));
+        saida
+    }
+}
+
+impl fmt::Display for ErroPSF {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.pos {
+            Some(pos) => write!(f, "{}: {}", pos, self.mensagem),
+            None => write!(f, "{}", self.mensagem),
+        }
+    }
+}
+
+pub enum Passo {
+    Continuar,
+    Fim,
+    Yield(Item),
+    // recv num canal vazio, a tarefa espera ate chegar algo
+    Bloqueado(Rc<Canal>),
+}
+
+// ---------- Geradores ----------
+
+#[derive(Debug)]
+pub enum Gerador {
+    // corpo rodando com stack e stack de consumo proprias, parado num yield
+    Codigo {
+        stack: Stack<Item>,
+        stack_consumir: Stack<No>,
+    },
+    Map(Rc<RefCell<Gerador>>, Func),
+    Filter(Rc<RefCell<Gerador>>, Func),
+    // dentro de um next, pra detectar gerador chamando ele mesmo
+    Rodando,
+    Fim,
+}
+
+// roda o gerador ate o proximo yield, None quando ele termina
+fn proximo(estado: &mut PSFState, gerador: &Rc<RefCell<Gerador>>) -> Result<Option<Item>, ErroPSF> {
+    let atual = std::mem::replace(&mut *gerador.borrow_mut(), Gerador::Rodando);
+    let (resultado, depois) = match atual {
+        Gerador::Codigo {
+            mut stack,
+            mut stack_consumir,
+        } => {
+            std::mem::swap(&mut estado.stack, &mut stack);
+            let anterior = std::mem::replace(&mut estado.em_gerador, true);
+            let resultado = executar_ate_yield(estado, &mut stack_consumir);
+            estado.em_gerador = anterior;
+            std::mem::swap(&mut estado.stack, &mut stack);
+            match resultado {
+                Ok(Some(valor)) => (
+                    Ok(Some(valor)),
+                    Gerador::Codigo {
+                        stack,
+                        stack_consumir,
+                    },
+                ),
+                Ok(None) => (Ok(None), Gerador::Fim),
+                Err(erro) => (Err(erro), Gerador::Fim),
+            }
+        }
+        Gerador::Map(origem, f) => {
+            let resultado = match proximo(estado, &origem) {
+                Ok(Some(valor)) => aplicar(estado, &f, valor).map(Some),
+                outro => outro,
+            };
+            (resultado, Gerador::Map(origem, f))
+        }
+        Gerador::Filter(origem, f) => {
+            let resultado = loop {
+                match proximo(estado, &origem) {
+                    Ok(Some(valor)) => match filtrar(estado, &f, &valor) {
+                        Ok(true) => break Ok(Some(valor)),
+                        Ok(false) => {}
+                        Err(erro) => break Err(erro),
+                    },
+                    outro => break outro,
+                }
+            };
+            (resultado, Gerador::Filter(origem, f))
+        }
+        Gerador::Rodando => {
+            return Err(ErroPSF::new(
+                "gerador",
+                "gerador chamou next nele mesmo".to_owned(),
+            ));
+        }
+        Gerador::Fim => (Ok(None), Gerador::Fim),
+    };
+    *gerador.borrow_mut() = depois;
+    resultado
+}
+
+// roda f numa stack separada contendo so o valor e devolve o topo
+fn aplicar(estado: &mut PSFState, f: &Func, valor: Item) -> Result<Item, ErroPSF> {
+    let mut stack = Stack::new();
+    stack.push(valor);
+    std::mem::swap(&mut estado.stack, &mut stack);
+    let anterior = std::mem::replace(&mut estado.em_gerador, false);
+    let mut stack_consumir = Stack::new();
+    empilhar(&mut stack_consumir, f);
+    let resultado = executar_ate_yield(estado, &mut stack_consumir);
+    estado.em_gerador = anterior;
+    std::mem::swap(&mut estado.stack, &mut stack);
+    resultado?;
+    match stack.pop() {
+        Some(item) => Ok(item),
+        None => lancar!("stack-vazia", "funcao do gerador nao deixou valor na stack"),
+    }
+}
+
+// o filtro do filter, aplicado igual em gerador e em lista
+fn filtrar(estado: &mut PSFState, f: &Func, valor: &Item) -> Result<bool, ErroPSF> {
+    match aplicar(estado, f, valor.clone())? {
+        Item::Bool(b) => Ok(b),
+        outro => lancar!(
+            "tipo",
+            "filtro do filter retornou {} em vez de bool",
+            outro.tipo()
+        ),
+    }
+}
+
+// ---------- Tarefas ----------
+
+// fila sem limite, send nunca bloqueia e recv bloqueia enquanto ta vazia
+#[derive(Debug, Default)]
+pub struct Canal {
+    fila: RefCell<VecDeque<Item>>,
+}
+
+// tarefa parada na fila do escalonador
+#[derive(Debug)]
+pub struct Tarefa {
+    id: usize,
+    stack: Stack<Item>,
+    stack_consumir: Stack<No>,
+    esperando: Option<Rc<Canal>>,
+}
+
+impl Tarefa {
+    fn pode_rodar(&self) -> bool {
+        match &self.esperando {
+            Some(canal) => !canal.fila.borrow().is_empty(),
+            None => true,
+        }
+    }
+}
+
+// ---------- Execucao ----------
+
+fn empilhar(stack_consumir: &mut Stack<No>, func: &Func) {
+    stack_consumir.lista.extend(func.iter().rev().cloned());
+}
+
+// ve cada passo antes do item rodar, usado pelo --trace, --explain e --debug
+pub trait Observador: fmt::Debug {
+    // stack_consumir ja sem o no que vai rodar; Err para a execucao
+    fn passo(
+        &mut self,
+        estado: &mut PSFState,
+        no: &No,
+        stack_consumir: &Stack<No>,
+    ) -> Result<(), ErroPSF>;
+}
+
+fn observar(estado: &mut PSFState, no: &No, stack_consumir: &Stack<No>) -> Result<(), ErroPSF> {
+    let mut observadores = std::mem::take(&mut estado.observadores);
+    let resultado = observadores
+        .iter_mut()
+        .try_for_each(|observador| observador.passo(estado, no, stack_consumir));
+    estado.observadores = observadores;
+    resultado
+}
+
+// executa um item da stack de consumo
+pub fn passo(estado: &mut PSFState, stack_consumir: &mut Stack<No>) -> Result<Passo, ErroPSF> {
+    let Some(no) = stack_consumir.pop() else {
+        return Ok(Passo::Fim);
+    };
+    // sem limites nem observadores nao tem o que conferir antes do item
+    let conferir = match estado.observadores.is_empty() {
+        true if estado.limites.nenhum() => Ok(()),
+        true => verificar_limites(estado, stack_consumir),
+        false => verificar_limites(estado, stack_consumir)
+            .and_then(|()| observar(estado, &no, stack_consumir)),
+    };
+    let resultado = conferir.and_then(|()| executar_item(estado, stack_consumir, no.item, &no.pos));
+    match resultado {
+        Ok(Passo::Bloqueado(canal)) => {
+            // o recv volta pra stack de consumo pra ser tentado de novo
+            stack_consumir.push(No {
+                item: ASTItem::Recv,
+                pos: no.pos,
+            });
+            Ok(Passo::Bloqueado(canal))
+        }
+        Ok(resultado) => Ok(resultado),
+        Err(mut erro) => {
+            if erro.pos.is_none() {
+                erro.pos = no.pos.as_deref().cloned();
+            }
+            // erro relancado ou vindo de um gerador ja tem o rastro de onde aconteceu
+            if erro.contexto.is_none() {
+                let tam = estado.stack.len();
+                erro.contexto = Some(Box::new(ContextoErro {
+                    rastro: cadeia_chamadas(stack_consumir)
+                        .into_iter()
+                        .cloned()
+                        .collect(),
+                    stack: estado.stack.lista[tam.saturating_sub(ITENS_RELATORIO)..].to_vec(),
+                    tam_stack: tam,
+                }));
+            }
+            Err(erro)
+        }
+    }
+}
+
+// funcoes nomeadas em execucao, da mais externa pra mais interna
+pub fn cadeia_chamadas(stack_consumir: &Stack<No>) -> Vec<&Quadro> {
+    stack_consumir
+        .lista
+        .iter()
+        .filter_map(|no| match &no.item {
+            ASTItem::FimChamada(quadro) => Some(quadro),
+            _ => None,
+        })
+        .collect()
+}
+
+// niveis de chamada da cadeia, a de cauda reaproveita o quadro mas conta como
+// mais um nivel
+pub fn profundidade(cadeia: &[&Quadro]) -> usize {
+    cadeia.iter().map(|quadro| 1 + quadro.caudas).sum()
+}
+
+fn verificar_limites(estado: &mut PSFState, stack_consumir: &Stack<No>) -> Result<(), ErroPSF> {
+    estado.passos += 1;
+    let limites = &estado.limites;
+    let excedido = if limites.stack.is_some_and(|max| estado.stack.len() > max) {
+        format!(
+            "limite de tamanho da stack excedido ({} itens)",
+            estado.stack.len()
+        )
+    } else if limites
+        .continuacao
+        .is_some_and(|max| stack_consumir.len() > max)
+    {
+        format!(
+            "limite de profundidade da continuacao excedido ({} itens)",
+            stack_consumir.len()
+        )
+    } else if limites.passos.is_some_and(|max| estado.passos > max) {
+        format!("limite de passos excedido ({})", estado.passos - 1)
+    } else if !estado.passos.is_multiple_of(1024) {
+        // tempo e memoria sao mais caros de medir
+        return Ok(());
+    } else if limites
+        .tempo
+        .is_some_and(|max| estado.inicio.elapsed() > max)
+    {
+        format!(
+            "limite de tempo excedido ({} ms)",
+            estado.inicio.elapsed().as_millis()
+        )
+    } else if let Some(max) = limites.memoria
+        && memoria_aprox(estado, stack_consumir) > max
+    {
+        format!(
+            "limite de memoria excedido (~{} bytes)",
+            memoria_aprox(estado, stack_consumir)
+        )
+    } else {
+        return Ok(());
+    };
+    lancar!("limite", "{}", excedido);
+}
+
+// estimativa grosseira do que a stack e a stack de consumo ocupam
+fn memoria_aprox(estado: &PSFState, stack_consumir: &Stack<No>) -> usize {
+    fn func(f: &Func) -> usize {
+        f.iter().map(no).sum()
+    }
+    fn no(no: &No) -> usize {
+        std::mem::size_of::<No>()
+            + match &no.item {
+                ASTItem::String(s) | ASTItem::FuncCallNamed(s) => s.len(),
+                ASTItem::FuncDef(f) | ASTItem::FimTry(f, _) => func(f),
+                ASTItem::Valor(v) => item(v),
+                _ => 0,
+            }
+    }
+    fn item(i: &Item) -> usize {
+        std::mem::size_of::<Item>()
+            + match i {
+                Item::String(s) | Item::Palavra(s) => s.len(),
+                Item::Func(f) => func(f),
+                Item::Lista(l) => l.iter().map(item).sum(),
+                _ => 0,
+            }
+    }
+    estado.stack.lista.iter().map(item).sum::<usize>()
+        + stack_consumir.lista.iter().map(no).sum::<usize>()
+}
+
+// desempilha ate o try mais proximo e chama o tratador com o erro
+fn tratar_erro(
+    estado: &mut PSFState,
+    stack_consumir: &mut Stack<No>,
+    erro: ErroPSF,
+) -> Result<(), ErroPSF> {
+    // limite estourado e execucao interrompida pelo depurador nao sao pegos por try
+    if erro.tipo == "limite" || erro.tipo == "interrompido" {
+        return Err(erro);
+    }
+    while let Some(no) = stack_consumir.pop() {
+        if let ASTItem::FimTry(tratador, tam_stack) = no.item {
+            estado.stack.lista.truncate(tam_stack);
+            estado.stack.push(Item::Erro(Box::new(erro)));
+            empilhar(stack_consumir, &tratador);
+            return Ok(());
+        }
+    }
+    Err(erro)
+}
+
+// roda ate acabar ou ate um yield, o yield so e aceito dentro de um gerador
+fn executar_ate_yield(
+    estado: &mut PSFState,
+    stack_consumir: &mut Stack<No>,
+) -> Result<Option<Item>, ErroPSF> {
+    loop {
+        match passo(estado, stack_consumir) {
+            Ok(Passo::Continuar) => {}
+            Ok(Passo::Fim) => return Ok(None),
+            Ok(Passo::Yield(valor)) => return Ok(Some(valor)),
+            Ok(Passo::Bloqueado(_)) => {
+                // sem escalonador aqui, ninguem mais vai mandar nada pro canal
+                lancar!("tarefa", "recv bloqueado fora do escalonador de tarefas");
+            }
+            Err(erro) => tratar_erro(estado, stack_consumir, erro)?,
+        }
+    }
+}
+
+// roda a tarefa atual e as criadas com spawn, trocando a cada quantum de passos
+// ou quando a atual bloqueia, ate todas acabarem
+pub fn executar(estado: &mut PSFState, stack_consumir: &mut Stack<No>) -> Result<(), ErroPSF> {
+    let mut passos = 0;
+    loop {
+        let mut esperando: Option<Rc<Canal>> = None;
+        let mut parada = false;
+        let trocar = match passo(estado, stack_consumir) {
+            Ok(Passo::Continuar) => {
+                passos += 1;
+                passos >= estado.quantum
+            }
+            Ok(Passo::Fim) => {
+                if estado.tarefas.is_empty() {
+                    return Ok(());
+                }
+                parada = true;
+                true
+            }
+            Ok(Passo::Yield(_)) => unreachable!("yield so roda dentro de gerador"),
+            Ok(Passo::Bloqueado(canal)) => {
+                esperando = Some(canal);
+                parada = true;
+                true
+            }
+            Err(erro) => {
+                tratar_erro(estado, stack_consumir, erro)?;
+                false
+            }
+        };
+        if !trocar {
+            continue;
+        }
+        passos = 0;
+        // proxima tarefa que pode rodar, na ordem da fila
+        let Some(i) = estado.tarefas.iter().position(|t| t.pode_rodar()) else {
+            if parada {
+                lancar!(
+                    "tarefa",
+                    "deadlock: todas as tarefas estao esperando um canal"
+                );
+            }
+            continue;
+        };
+        let proxima = estado.tarefas.remove(i).unwrap();
+        let atual = Tarefa {
+            id: estado.tarefa_atual,
+            stack: std::mem::replace(&mut estado.stack, proxima.stack),
+            stack_consumir: std::mem::replace(stack_consumir, proxima.stack_consumir),
+            esperando,
+        };
+        estado.tarefa_atual = proxima.id;
+        if !atual.stack_consumir.is_empty() {
+            estado.tarefas.push_back(atual);
+        }
+    }
+}
+
+pub fn interpretar_func(estado: &mut PSFState, func: Func) -> Result<(), ErroPSF> {
+    let mut stack_consumir = Stack::new();
+    empilhar(&mut stack_consumir, &func);
+    executar(estado, &mut stack_consumir)
+}
+
+fn executar_item(
+    estado: &mut PSFState,
+    stack_consumir: &mut Stack<No>,
+    item: ASTItem,
+    pos: &Option<Rc<Pos>>,
+) -> Result<Passo, ErroPSF> {
+    match item {
+        ASTItem::True => {
+            estado.stack.push(Item::Bool(true));
+        }
+        ASTItem::False => {
+            estado.stack.push(Item::Bool(false));
+        }
+        ASTItem::Numero(n) => {
+            estado.stack.push(Item::Numero(n));
+        }
+        ASTItem::String(s) => {
+            estado.stack.push(Item::String(s));
+        }
+        ASTItem::Valor(v) => {
+            estado.stack.push(v);
+        }
+        ASTItem::Mais => {
+            let n1 = estado.pop_numero("+")?;
+            let n2 = estado.pop_numero("+")?;
+            let Some(soma) = n1.checked_add(n2) else {
+                lancar!("aritmetica", "overflow somando {} e {}", n2, n1);
+            };
+            estado.stack.push(Item::Numero(soma));
+        }
+        ASTItem::Maior => {
+            let n1 = estado.pop_numero(">")?;
+            let n2 = estado.pop_numero(">")?;
+            estado.stack.push(Item::Numero(n2));
+            estado.stack.push(Item::Numero(n1));
+            estado.stack.push(Item::Bool(n2 > n1));
+        }
+        ASTItem::Menor => {
+            let n1 = estado.pop_numero("<")?;
+            let n2 = estado.pop_numero("<")?;
+            estado.stack.push(Item::Numero(n2));
+            estado.stack.push(Item::Numero(n1));
+            estado.stack.push(Item::Bool(n2 < n1));
+        }
+        ASTItem::Print => {
+            let item = estado.pop("print")?;
+            estado.stack.push(item.clone());
+            estado.escrever(&format!("{}\n", item));
+        }
+        ASTItem::Input => {
+            let s = estado.pop_string("input")?;
+            estado.escrever(&s);
+            let input = estado.ler_linha();
+            estado.stack.push(Item::String(input));
+        }
+        ASTItem::Pop => {
+            _ = estado.stack.pop();
+        }
+        ASTItem::Dup => {
+            let topo = estado.pop("dup")?;
+            estado.stack.push(topo.clone());
+            estado.stack.push(topo);
+        }
+        ASTItem::Swap => {
+            let prim = estado.pop("swap")?;
+            let seg = estado.pop("swap")?;
+            estado.stack.push(prim);
+            estado.stack.push(seg);
+        }
+        ASTItem::SwapN => {
+            let pos = estado.pop_numero("swapn")?;
+            if pos < 0 {
+                lancar!("indice", "impossivel trocar posicao negativa: {}", pos);
+            }
+            let tam = estado.stack.len() as i32;
+            if pos >= tam {
+                lancar!(
+                    "indice",
+                    "tentando indexar {} mas a stack ta com tamanho {}",
+                    pos,
+                    tam
+                );
+            } else if pos == 0 {
+                return Ok(Passo::Continuar);
+            }
+            let topi = estado.stack.lista[tam as usize - 1].clone();
+            let nth = estado.stack.lista[tam as usize - 1 - pos as usize].clone();
+            estado.stack.lista[tam as usize - 1] = nth;
+            estado.stack.lista[tam as usize - 1 - pos as usize] = topi;
+        }
+        ASTItem::SSize => {
+            estado.stack.push(Item::Numero(estado.stack.len() as i32));
+        }
+        ASTItem::If => {
+            let ff = estado.pop_func("if")?;
+            let fv = estado.pop_func("if")?;
+            let bo = estado.pop_bool("if")?;
+            if bo {
+                estado.stack.push(Item::Func(fv));
+            } else {
+                estado.stack.push(Item::Func(ff));
+            }
+            stack_consumir.push(No::sem_pos(ASTItem::FuncCallTop));
+        }
+        ASTItem::DebugS => {
+            estado.escrever(&format!("debug: {:?}\n", estado.stack));
+        }
+        ASTItem::Break => {}
+        ASTItem::Curry => {
+            // x ?(q) curry -> ?(x q)
+            let f = estado.pop_func("curry")?;
+            let valor = estado.pop("curry")?;
+            let mut nova = vec![No::sem_pos(ASTItem::Valor(valor))];
+            nova.extend(f);
+            estado.stack.push(Item::Func(nova));
+        }
+        ASTItem::Curry2 => {
+            // x y ?(q) curry2 -> ?(x y q)
+            let f = estado.pop_func("curry2")?;
+            let v2 = estado.pop("curry2")?;
+            let v1 = estado.pop("curry2")?;
+            let mut nova = vec![
+                No::sem_pos(ASTItem::Valor(v1)),
+                No::sem_pos(ASTItem::Valor(v2)),
+            ];
+            nova.extend(f);
+            estado.stack.push(Item::Func(nova));
+        }
+        ASTItem::Compose => {
+            // ?(a) ?(b) compose -> ?(a b)
+            let f2 = estado.pop_func("compose")?;
+            let mut f1 = estado.pop_func("compose")?;
+            f1.extend(f2);
+            estado.stack.push(Item::Func(f1));
+        }
+        ASTItem::With => {
+            // x ?(q) with -> ?(x swap q), o x fica embaixo do argumento
+            let f = estado.pop_func("with")?;
+            let valor = estado.pop("with")?;
+            let mut nova = vec![
+                No::sem_pos(ASTItem::Valor(valor)),
+                No::sem_pos(ASTItem::Swap),
+            ];
+            nova.extend(f);
+            estado.stack.push(Item::Func(nova));
+        }
+        ASTItem::ToList => {
+            let f = estado.pop_func("tolist")?;
+            estado.stack.push(Item::Lista(
+                f.iter().map(|no| ast_para_item(&no.item)).collect(),
+            ));
+        }
+        ASTItem::ToQuot => {
+            let l = match estado.pop("toquot")? {
+                Item::Lista(l) => l,
+                outro => lancar!("tipo", "toquot esperava lista mas recebeu {}", outro.tipo()),
+            };
+            estado.stack.push(Item::Func(
+                l.into_iter()
+                    .map(|i| No::sem_pos(item_para_ast(i)))
+                    .collect(),
+            ));
+        }
+        ASTItem::Size => {
+            let tam = match estado.pop("size")? {
+                Item::Func(f) => f.len(),
+                Item::Lista(l) => l.len(),
+                outro => lancar!("tipo", "size so aceita funcao ou lista: {}", outro.tipo()),
+            };
+            estado.stack.push(Item::Numero(tam as i32));
+        }
+        ASTItem::First => {
+            let prim = match estado.pop("first")? {
+                Item::Func(f) => f.first().map(|no| ast_para_item(&no.item)),
+                Item::Lista(l) => l.into_iter().next(),
+                outro => lancar!("tipo", "first so aceita funcao ou lista: {}", outro.tipo()),
+            };
+            let Some(prim) = prim else {
+                lancar!("indice", "first de funcao ou lista vazia");
+            };
+            estado.stack.push(prim);
+        }
+        ASTItem::Rest => match estado.pop("rest")? {
+            Item::Func(f) if !f.is_empty() => {
+                estado.stack.push(Item::Func(f[1..].to_vec()));
+            }
+            Item::Lista(l) if !l.is_empty() => {
+                estado.stack.push(Item::Lista(l[1..].to_vec()));
+            }
+            Item::Func(_) | Item::Lista(_) => {
+                lancar!("indice", "rest de funcao ou lista vazia");
+            }
+            outro => lancar!("tipo", "rest so aceita funcao ou lista: {}", outro.tipo()),
+        },
+        ASTItem::Append => {
+            let valor = estado.pop("append")?;
+            match estado.pop("append")? {
+                Item::Func(mut f) => {
+                    f.push(No::sem_pos(item_para_ast(valor)));
+                    estado.stack.push(Item::Func(f));
+                }
+                Item::Lista(mut l) => {
+                    l.push(valor);
+                    estado.stack.push(Item::Lista(l));
+                }
+                outro => lancar!("tipo", "append so aceita funcao ou lista: {}", outro.tipo()),
+            }
+        }
+        ASTItem::Word => {
+            let s = estado.pop_string("word")?;
+            estado.stack.push(Item::Palavra(s));
+        }
+        ASTItem::Eval => {
+            // sucesso: roda o codigo e empilha true, erro: empilha mensagem e false
+            let codigo = estado.pop_string("eval")?;
+            match tokenizar_e_gerar_ast(&codigo, "<eval>", true) {
+                Ok(itens) => {
+                    // erro ao rodar tambem volta como valor, igual ao try: ?(errmsg false)
+                    let tratador = vec![No::sem_pos(ASTItem::ErrMsg), No::sem_pos(ASTItem::False)];
+                    stack_consumir.push(No::sem_pos(ASTItem::FimTry(tratador, estado.stack.len())));
+                    stack_consumir.push(No::sem_pos(ASTItem::True));
+                    for definicao in itens {
+                        empilhar(stack_consumir, &definicao.corpo);
+                    }
+                }
+                Err(erro) => {
+                    estado.stack.push(Item::String(erro.to_string()));
+                    estado.stack.push(Item::Bool(false));
+                }
+            }
+        }
+        ASTItem::Define => {
+            let f = estado.pop_func("define")?;
+            let nome = estado.pop_string("define")?;
+            match validar_nome(&nome) {
+                Ok(()) => {
+                    estado.funcoes.insert(nome, f);
+                    estado.stack.push(Item::Bool(true));
+                }
+                Err(erro) => {
+                    estado.stack.push(Item::String(erro));
+                    estado.stack.push(Item::Bool(false));
+                }
+            }
+        }
+        ASTItem::Throw => match estado.pop("throw")? {
+            Item::Erro(erro) => return Err(*erro),
+            Item::String(s) => lancar!("usuario", "{}", s),
+            outro => lancar!("usuario", "{}", outro),
+        },
+        ASTItem::Try => {
+            // ?(corpo) ?(tratador) try
+            let tratador = estado.pop_func("try")?;
+            let corpo = estado.pop_func("try")?;
+            stack_consumir.push(No::sem_pos(ASTItem::FimTry(tratador, estado.stack.len())));
+            empilhar(stack_consumir, &corpo);
+        }
+        ASTItem::Error => {
+            // "tipo" "mensagem" error
+            let mensagem = estado.pop_string("error")?;
+            let tipo = estado.pop_string("error")?;
+            estado
+                .stack
+                .push(Item::Erro(Box::new(ErroPSF::new(&tipo, mensagem))));
+        }
+        ASTItem::ErrKind => {
+            let erro = estado.pop_erro("errkind")?;
+            estado.stack.push(Item::String(erro.tipo));
+        }
+        ASTItem::ErrMsg => {
+            let erro = estado.pop_erro("errmsg")?;
+            estado.stack.push(Item::String(erro.mensagem));
+        }
+        ASTItem::ErrPos => {
+            let erro = estado.pop_erro("errpos")?;
+            let pos = match erro.pos {
+                Some(pos) => pos.to_string(),
+                None => String::new(),
+            };
+            estado.stack.push(Item::String(pos));
+        }
+        ASTItem::Gen => {
+            let f = estado.pop_func("gen")?;
+            let mut stack_consumir = Stack::new();
+            empilhar(&mut stack_consumir, &f);
+            estado
+                .stack
+                .push(Item::Gerador(Rc::new(RefCell::new(Gerador::Codigo {
+                    stack: Stack::new(),
+                    stack_consumir,
+                }))));
+        }
+        ASTItem::Yield => {
+            if !estado.em_gerador {
+                lancar!("gerador", "yield fora de um gerador");
+            }
+            let valor = estado.pop("yield")?;
+            return Ok(Passo::Yield(valor));
+        }
+        ASTItem::Next => {
+            let gerador = estado.pop_gerador("next")?;
+            match proximo(estado, &gerador)? {
+                Some(valor) => {
+                    estado.stack.push(valor);
+                    estado.stack.push(Item::Bool(true));
+                }
+                None => estado.stack.push(Item::Bool(false)),
+            }
+        }
+        ASTItem::Take => {
+            let n = estado.pop_numero("take")?;
+            let lista = match estado.pop("take")? {
+                Item::Gerador(gerador) => {
+                    let mut lista = vec![];
+                    while (lista.len() as i32) < n {
+                        match proximo(estado, &gerador)? {
+                            Some(valor) => lista.push(valor),
+                            None => break,
+                        }
+                    }
+                    lista
+                }
+                Item::Lista(l) => l.into_iter().take(n.max(0) as usize).collect(),
+                outro => lancar!("tipo", "take so aceita gerador ou lista: {}", outro.tipo()),
+            };
+            estado.stack.push(Item::Lista(lista));
+        }
+        ASTItem::Map => {
+            let f = estado.pop_func("map")?;
+            match estado.pop("map")? {
+                Item::Gerador(origem) => {
+                    estado
+                        .stack
+                        .push(Item::Gerador(Rc::new(RefCell::new(Gerador::Map(
+                            origem, f,
+                        )))));
+                }
+                // cada valor numa stack separada, como no gerador
+                Item::Lista(l) => {
+                    let lista = l
+                        .into_iter()
+                        .map(|valor| aplicar(estado, &f, valor))
+                        .collect::<Result<_, _>>()?;
+                    estado.stack.push(Item::Lista(lista));
+                }
+                outro => lancar!("tipo", "map so aceita gerador ou lista: {}", outro.tipo()),
+            }
+        }
+        ASTItem::Filter => {
+            let f = estado.pop_func("filter")?;
+            match estado.pop("filter")? {
+                Item::Gerador(origem) => {
+                    estado
+                        .stack
+                        .push(Item::Gerador(Rc::new(RefCell::new(Gerador::Filter(
+                            origem, f,
+                        )))));
+                }
+                Item::Lista(l) => {
+                    let mut lista = vec![];
+                    for valor in l {
+                        if filtrar(estado, &f, &valor)? {
+                            lista.push(valor);
+                        }
+                    }
+                    estado.stack.push(Item::Lista(lista));
+                }
+                outro => lancar!(
+                    "tipo",
+                    "filter so aceita gerador ou lista: {}",
+                    outro.tipo()
+                ),
+            }
+        }
+        ASTItem::Each => {
+            let f = estado.pop_func("each")?;
+            match estado.pop("each")? {
+                Item::Gerador(gerador) => {
+                    stack_consumir.push(No::sem_pos(ASTItem::EachGerador(gerador, f)));
+                }
+                Item::Lista(l) => {
+                    for valor in l.into_iter().rev() {
+                        empilhar(stack_consumir, &f);
+                        stack_consumir.push(No::sem_pos(ASTItem::Valor(valor)));
+                    }
+                }
+                outro => lancar!("tipo", "each so aceita gerador ou lista: {}", outro.tipo()),
+            }
+        }
+        ASTItem::CallCC | ASTItem::CallCCS => {
+            // ?(k ...) callcc, k e o que viria depois do callcc
+            let f = estado.pop_func("callcc")?;
+            let stack = match item {
+                ASTItem::CallCCS => Some(estado.stack.clone()),
+                _ => None,
+            };
+            estado.stack.push(Item::Continuacao(Rc::new(Continuacao {
+                stack_consumir: stack_consumir.clone(),
+                stack,
+            })));
+            empilhar(stack_consumir, &f);
+        }
+        ASTItem::Spawn => {
+            let f = estado.pop_func("spawn")?;
+            let mut stack_consumir = Stack::new();
+            empilhar(&mut stack_consumir, &f);
+            estado.proxima_tarefa += 1;
+            estado.tarefas.push_back(Tarefa {
+                id: estado.proxima_tarefa,
+                stack: Stack::new(),
+                stack_consumir,
+                esperando: None,
+            });
+        }
+        ASTItem::Chan => {
+            estado.stack.push(Item::Canal(Rc::new(Canal::default())));
+        }
+        ASTItem::Send => {
+            // canal valor send -> canal
+            let valor = estado.pop("send")?;
+            let canal = estado.pop_canal("send")?;
+            canal.fila.borrow_mut().push_back(valor);
+            estado.stack.push(Item::Canal(canal));
+        }
+        ASTItem::Recv => {
+            // canal recv -> canal valor
+            let canal = estado.pop_canal("recv")?;
+            let valor = canal.fila.borrow_mut().pop_front();
+            let Some(valor) = valor else {
+                estado.stack.push(Item::Canal(canal.clone()));
+                return Ok(Passo::Bloqueado(canal));
+            };
+            estado.stack.push(Item::Canal(canal));
+            estado.stack.push(valor);
+        }
+        ASTItem::Assert => {
+            if !estado.pop_bool("assert")? {
+                lancar!("assert", "assert falhou");
+            }
+        }
+        ASTItem::AssertEq => {
+            // valor esperado assert-eq
+            let esperado = estado.pop("assert-eq")?;
+            let valor = estado.pop("assert-eq")?;
+            if !mesmo_item(&valor, &esperado) {
+                lancar!(
+                    "assert",
+                    "assert-eq: esperava {} mas veio {}",
+                    formatar_itens(&[esperado]),
+                    formatar_itens(&[valor])
+                );
+            }
+        }
+        ASTItem::AssertStack => {
+            // ?(1 2 3) assert-stack: a stack inteira, de baixo pra cima
+            let esperado: Vec<Item> = match estado.pop("assert-stack")? {
+                Item::Func(f) => f.iter().map(|no| ast_para_item(&no.item)).collect(),
+                Item::Lista(l) => l,
+                outro => lancar!(
+                    "tipo",
+                    "assert-stack esperava funcao ou lista mas recebeu {}",
+                    outro.tipo()
+                ),
+            };
+            let atual = &estado.stack.lista;
+            let iguais = atual
+                .iter()
+                .zip(&esperado)
+                .take_while(|(a, b)| mesmo_item(a, b));
+            let iguais = iguais.count();
+            if iguais < esperado.len().max(atual.len()) {
+                lancar!(
+                    "assert",
+                    "assert-stack: a stack nao e a esperada\n    esperava: {}\n    veio:     {}\n    diferente a partir do item {} (de baixo pra cima)",
+                    formatar_itens(&esperado),
+                    formatar_itens(atual),
+                    iguais + 1
+                );
+            }
+        }
+        ASTItem::FimTry(_, _) => {}
+        ASTItem::EachGerador(gerador, f) => {
+            if let Some(valor) = proximo(estado, &gerador)? {
+                stack_consumir.push(No::sem_pos(ASTItem::EachGerador(gerador, f.clone())));
+                empilhar(stack_consumir, &f);
+                stack_consumir.push(No::sem_pos(ASTItem::Valor(valor)));
+            }
+        }
+        ASTItem::FuncDef(f) => {
+            estado.stack.push(Item::Func(f));
+        }
+        ASTItem::FuncCallTop => match estado.pop("!")? {
+            Item::Func(f) => empilhar(stack_consumir, &f),
+            Item::Continuacao(k) => {
+                *stack_consumir = k.stack_consumir.clone();
+                if let Some(stack) = &k.stack {
+                    estado.stack = stack.clone();
+                }
+            }
+            outro => lancar!(
+                "tipo",
+                "! esperava funcao ou continuacao mas recebeu {}",
+                outro.tipo()
+            ),
+        },
+        ASTItem::FimChamada(quadro) => {
+            if let Some(base) = quadro.base {
+                conferir_saidas(estado, &quadro, base)?;
+            }
+        }
+        ASTItem::FuncCallNamed(nome) => {
+            let Some(f) = estado.funcoes.get(&nome) else {
+                lancar!("nome", "funcao `{}` nao existe", nome);
+            };
+            let base = match estado.efeitos.get(&nome) {
+                Some(efeito) if estado.verificar_efeitos => {
+                    Some(conferir_entradas(estado, &nome, efeito)?)
+                }
+                _ => None,
+            };
+            // chamada de cauda reaproveita o quadro de quem chamou, menos quando
+            // ele ainda tem que conferir o efeito no retorno
+            match stack_consumir.lista.last_mut() {
+                Some(No {
+                    item: ASTItem::FimChamada(anterior),
+                    ..
+                }) if anterior.base.is_none() => {
+                    anterior.nome = nome;
+                    anterior.pos.clone_from(pos);
+                    anterior.caudas += 1;
+                    anterior.base = base;
+                }
+                _ => stack_consumir.push(No::sem_pos(ASTItem::FimChamada(Quadro {
+                    nome,
+                    pos: pos.clone(),
+                    caudas: 0,
+                    base,
+                }))),
+            }
+            empilhar(stack_consumir, f);
+        }
+    }
+    Ok(Passo::Continuar)
+}
+
+// ---------- Efeitos ----------
+
+fn conferir_tipos(
+    estado: &PSFState,
+    nome: &str,
+    parametros: &[Parametro],
+    onde: &str,
+) -> Result<(), ErroPSF> {
+    let inicio = estado.stack.len() - parametros.len();
+    for (parametro, item) in parametros.iter().zip(&estado.stack.lista[inicio..]) {
+        if let Some(tipo) = &parametro.tipo
+            && tipo != item.tipo()
+        {
+            lancar!(
+                "efeito",
+                "`{}` {} `{}` do tipo {} mas recebeu {}",
+                nome,
+                onde,
+                parametro.nome,
+                tipo,
+                item.tipo()
+            );
+        }
+    }
+    Ok(())
+}
+
+// confere as entradas na chamada e devolve a altura da stack sem elas
+fn conferir_entradas(estado: &PSFState, nome: &str, efeito: &Efeito) -> Result<usize, ErroPSF> {
+    let tam = estado.stack.len();
+    if tam < efeito.entradas.len() {
+        lancar!(
+            "efeito",
+            "`{}` declara {} mas foi chamada com {} item(s) na stack",
+            nome,
+            efeito,
+            tam
+        );
+    }
+    conferir_tipos(estado, nome, &efeito.entradas, "esperava na entrada")?;
+    Ok(tam - efeito.entradas.len())
+}
+
+fn conferir_saidas(estado: &PSFState, quadro: &Quadro, base: usize) -> Result<(), ErroPSF> {
+    let Some(efeito) = estado.efeitos.get(&quadro.nome) else {
+        return Ok(());
+    };
+    let tam = estado.stack.len();
+    let esperado = base + efeito.saidas.len();
+    if tam != esperado {
+        return Err(ErroPSF {
+            pos: quadro.pos.as_deref().cloned(),
+            ..ErroPSF::new(
+                "efeito",
+                format!(
+                    "`{}` declara {} mas retornou com {} item(s) onde deveria ter {}",
+                    quadro.nome,
+                    efeito,
+                    tam as i64 - base as i64,
+                    efeito.saidas.len()
+                ),
+            )
+        });
+    }
+    conferir_tipos(estado, &quadro.nome, &efeito.saidas, "esperava na saida").map_err(|mut erro| {
+        erro.pos = quadro.pos.as_deref().cloned();
+        erro
+    })
+}
+
+// ---------- Estado ----------
+
+#[derive(Debug)]
+pub struct PSFState {
+    #[allow(dead_code, unused)]
+    stack: Stack<Item>,
+    #[allow(dead_code, unused)]
+    funcoes: HashMap<String, Func>,
+    // true enquanto roda o corpo de um gerador, yield so vale ai
+    em_gerador: bool,
+    // tarefas esperando a vez, a que ta rodando usa stack e a stack de consumo do executar
+    tarefas: VecDeque<Tarefa>,
+    tarefa_atual: usize,
+    proxima_tarefa: usize,
+    // quantos passos uma tarefa roda antes de passar a vez
+    quantum: usize,
+    limites: Limites,
+    // efeitos declarados, so conferidos com --check-effects
+    efeitos: HashMap<String, Efeito>,
+    verificar_efeitos: bool,
+    // passos e inicio da execucao atual, pros limites de passos e tempo
+    passos: u64,
+    inicio: Instant,
+    observadores: Vec<Box<dyn Observador>>,
+    saida: Saida,
+    // com o --debug as respostas do input ficam gravadas
+    entradas: Option<Entradas>,
+}
+
+// respostas do input na ordem, pra repetir a execucao igual quando o
+// depurador volta no tempo
+#[derive(Debug, Default)]
+pub struct Entradas {
+    gravadas: Vec<String>,
+    proxima: usize,
+    // sem terminal (o stdin e do dap): acabou as gravadas, o input le vazio
+    fechada: bool,
+}
+
+// pra onde vai o que o programa escreve com print, input e debugs
+#[derive(Debug, Default)]
+pub enum Saida {
+    #[default]
+    Terminal,
+    // guardada ate alguem pegar, o --explain mostra junto com o passo
+    Capturada(String),
+}
+
+// None e sem limite
+#[derive(Debug, Clone)]
+pub struct Limites {
+    pub stack: Option<usize>,
+    pub continuacao: Option<usize>,
+    pub passos: Option<u64>,
+    pub tempo: Option<Duration>,
+    // em bytes, aproximado
+    pub memoria: Option<usize>,
+}
+
+impl Limites {
+    // tudo desligado, o passo nem conta
+    pub fn nenhum(&self) -> bool {
+        self.stack.is_none()
+            && self.continuacao.is_none()
+            && self.passos.is_none()
+            && self.tempo.is_none()
+            && self.memoria.is_none()
+    }
+}
+
+impl Default for Limites {
+    fn default() -> Self {
+        Limites {
+            stack: Some(1000),
+            continuacao: Some(100_000),
+            passos: None,
+            tempo: None,
+            memoria: None,
+        }
+    }
+}
+
+impl PSFState {
+    pub fn new() -> PSFState {
+        PSFState {
+            stack: Stack::new(),
+            funcoes: HashMap::new(),
+            em_gerador: false,
+            tarefas: VecDeque::new(),
+            tarefa_atual: 0,
+            proxima_tarefa: 0,
+            quantum: 100,
+            limites: Limites::default(),
+            efeitos: HashMap::new(),
+            verificar_efeitos: false,
+            passos: 0,
+            inicio: Instant::now(),
+            observadores: vec![],
+            saida: Saida::Terminal,
+            entradas: None,
+        }
+    }
+
+    // estado novo com as mesmas funcoes e opcoes, pra rodar de novo do comeco
+    pub fn recomecar(&self) -> PSFState {
+        PSFState {
+            funcoes: self.funcoes.clone(),
+            quantum: self.quantum,
+            limites: self.limites.clone(),
+            efeitos: self.efeitos.clone(),
+            verificar_efeitos: self.verificar_efeitos,
+            ..PSFState::new()
+        }
+    }
+
+    // proxima linha do stdin, ou a gravada se essa execucao e uma repeticao
+    fn ler_linha(&mut self) -> String {
+        if let Some(entradas) = &mut self.entradas
+            && let Some(gravada) = entradas.gravadas.get(entradas.proxima)
+        {
+            entradas.proxima += 1;
+            return gravada.clone();
+        }
+        if self.entradas.as_ref().is_some_and(|e| e.fechada) {
+            return String::new();
+        }
+        let mut linha = String::new();
+        _ = io::stdout().flush();
+        io::stdin().read_line(&mut linha).unwrap();
+        if let Some(entradas) = &mut self.entradas {
+            entradas.gravadas.push(linha.clone());
+            entradas.proxima += 1;
+        }
+        linha
+    }
+
+    fn escrever(&mut self, texto: &str) {
+        match &mut self.saida {
+            Saida::Terminal => print!("{}", texto),
+            Saida::Capturada(capturada) => capturada.push_str(texto),
+        }
+    }
+
+    pub fn clear_stack(&mut self) {
+        self.stack.lista.clear();
+    }
+
+    fn pop(&mut self, palavra: &str) -> Result<Item, ErroPSF> {
+        match self.stack.pop() {
+            Some(item) => Ok(item),
+            None => lancar!("stack-vazia", "stack vazia na chamada do {}", palavra),
+        }
+    }
+
+    fn pop_numero(&mut self, palavra: &str) -> Result<i32, ErroPSF> {
+        match self.pop(palavra)? {
+            Item::Numero(n) => Ok(n),
+            outro => lancar!(
+                "tipo",
+                "{} esperava numero mas recebeu {}",
+                palavra,
+                outro.tipo()
+            ),
+        }
+    }
+
+    fn pop_bool(&mut self, palavra: &str) -> Result<bool, ErroPSF> {
+        match self.pop(palavra)? {
+            Item::Bool(b) => Ok(b),
+            outro => lancar!(
+                "tipo",
+                "{} esperava bool mas recebeu {}",
+                palavra,
+                outro.tipo()
+            ),
+        }
+    }
+
+    fn pop_string(&mut self, palavra: &str) -> Result<String, ErroPSF> {
+        match self.pop(palavra)? {
+            Item::String(s) => Ok(s),
+            outro => lancar!(
+                "tipo",
+                "{} esperava string mas recebeu {}",
+                palavra,
+                outro.tipo()
+            ),
+        }
+    }
+
+    fn pop_func(&mut self, palavra: &str) -> Result<Func, ErroPSF> {
+        match self.pop(palavra)? {
+            Item::Func(f) => Ok(f),
+            outro => lancar!(
+                "tipo",
+                "{} esperava funcao mas recebeu {}",
+                palavra,
+                outro.tipo()
+            ),
+        }
+    }
+
+    fn pop_gerador(&mut self, palavra: &str) -> Result<Rc<RefCell<Gerador>>, ErroPSF> {
+        match self.pop(palavra)? {
+            Item::Gerador(g) => Ok(g),
+            outro => lancar!(
+                "tipo",
+                "{} esperava gerador mas recebeu {}",
+                palavra,
+                outro.tipo()
+            ),
+        }
+    }
+
+    fn pop_canal(&mut self, palavra: &str) -> Result<Rc<Canal>, ErroPSF> {
+        match self.pop(palavra)? {
+            Item::Canal(c) => Ok(c),
+            outro => lancar!(
+                "tipo",
+                "{} esperava canal mas recebeu {}",
+                palavra,
+                outro.tipo()
+            ),
+        }
+    }
+
+    fn pop_erro(&mut self, palavra: &str) -> Result<ErroPSF, ErroPSF> {
+        match self.pop(palavra)? {
+            Item::Erro(e) => Ok(*e),
+            outro => lancar!(
+                "tipo",
+                "{} esperava erro mas recebeu {}",
+                palavra,
+                outro.tipo()
+            ),
+        }
+    }
+
+    #[allow(dead_code, unused)]
+    pub fn load_ast(&mut self, ast: AST) {
+        for definicao in ast {
+            match definicao.efeito {
+                Some(efeito) => self.efeitos.insert(definicao.nome.clone(), efeito),
+                None => self.efeitos.remove(&definicao.nome),
+            };
+            self.funcoes.insert(definicao.nome, definicao.corpo);
+        }
+    }
+
+    #[allow(dead_code, unused)]
+    pub fn load_string(&mut self, entrada: &str, arquivo: &str) {
+        let (ast, sintaxe) = analisar(entrada, arquivo);
+        for diagnostico in &sintaxe {
+            diagnostico.mostrar();
+        }
+        if !sintaxe.is_empty() {
+            log_error!("{} erro(s) de sintaxe em {}", sintaxe.len(), arquivo);
+        }
+        let diagnosticos = resolver(&ast, &self.funcoes);
+        for diagnostico in &diagnosticos {
+            diagnostico.mostrar();
+        }
+        let erros = diagnosticos
+            .iter()
+            .filter(|d| d.nivel == Nivel::Erro)
+            .count();
+        if erros > 0 {
+            log_error!("{} nome(s) nao resolvido(s) em {}", erros, arquivo);
+        }
+        self.load_ast(ast);
+    }
+
+    // so checa, sem rodar, e devolve quantos erros achou
+    pub fn check_string(&mut self, entrada: &str, arquivo: &str) -> usize {
+        let diagnosticos = self.diagnosticar(entrada, arquivo);
+        for diagnostico in &diagnosticos {
+            diagnostico.mostrar();
+        }
+        let erros = diagnosticos
+            .iter()
+            .filter(|d| d.nivel == Nivel::Erro)
+            .count();
+        log_info!(
+            "{}: {} erro(s), {} aviso(s)",
+            arquivo,
+            erros,
+            diagnosticos.len() - erros
+        );
+        erros
+    }
+
+    // tudo que o check acha, tambem usado pelo lsp a cada mudanca
+    pub fn diagnosticar(&self, entrada: &str, arquivo: &str) -> Vec<Diagnostico> {
+        let (ast, mut diagnosticos) = analisar(entrada, arquivo);
+        // com erro de sintaxe falta definicao e o resto so ia apontar falso erro
+        if diagnosticos.is_empty() {
+            diagnosticos.extend(resolver(&ast, &self.funcoes));
+            diagnosticos.extend(checagem::checar(&ast, &self.funcoes));
+            diagnosticos.extend(lint::lint(&ast, entrada, arquivo));
+            diagnosticos.sort_by_key(|d| d.pos.as_ref().map(|p| (p.linha, p.coluna)));
+        }
+        diagnosticos
+    }
+
+    #[allow(dead_code, unused)]
+    pub fn run_raw_string(&mut self, entrada: &str) {
+        let itens = match tokenizar_e_gerar_ast(entrada, "<repl>", true) {
+            Ok(itens) => itens,
+            Err(erro) => log_error!("{}", erro),
+        };
+        let funcao = itens.first().unwrap().corpo.to_vec();
+        self.comecar_execucao();
+        if let Err(erro) = interpretar_func(self, funcao) {
+            log_error!("{}", erro.relatorio());
+        }
+    }
+
+    pub fn explain_raw_string(&mut self, entrada: &str) {
+        let itens = match tokenizar_e_gerar_ast(entrada, "<repl>", true) {
+            Ok(itens) => itens,
+            Err(erro) => log_error!("{}", erro),
+        };
+        let funcao = itens.first().unwrap().corpo.to_vec();
+        if let Err(erro) = explicacao::explicar_expressao(self, funcao) {
+            log_error!("{}", erro.relatorio());
+        }
+    }
+
+    pub fn run_function(&mut self, f: &str) {
+        if let Err(erro) = self.executar_funcao(f) {
+            log_error!("{}", erro.relatorio());
+        }
+    }
+
+    // o erro volta pra quem chamou, que ainda tem algo pra mostrar antes dele
+    pub fn executar_funcao(&mut self, f: &str) -> Result<(), ErroPSF> {
+        // chamada nomeada pra main tambem aparecer na cadeia de chamadas
+        let chamada = vec![No::sem_pos(ASTItem::FuncCallNamed(f.to_owned()))];
+        self.comecar_execucao();
+        interpretar_func(self, chamada)
+    }
+
+    // zera os contadores dos limites de passos e tempo
+    fn comecar_execucao(&mut self) {
+        self.passos = 0;
+        self.inicio = Instant::now();
+    }
+
+    pub fn run_main(&mut self) {
+        self.run_function("main");
+    }
+
+    pub fn load_funcs(&mut self) {
+        self.funcoes.insert(
+            "printp".to_owned(),
+            vec![No::sem_pos(ASTItem::Print), No::sem_pos(ASTItem::Pop)],
+        );
+    }
+}
+
+impl Default for PSFState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// ---------- Helpers ----------
+
+fn tokenizar_e_gerar_ast(entrada: &str, arquivo: &str, funcao: bool) -> Result<AST, Diagnostico> {
+    gerar_ast(tokenizar(entrada, arquivo)?, funcao)
+}
+
+// todos os erros de sintaxe do arquivo, junto com o que deu pra montar da ast
+fn analisar(entrada: &str, arquivo: &str) -> (AST, Vec<Diagnostico>) {
+    let (tokens, mut erros) = tokenizar_tudo(entrada, arquivo);
+    let (ast, erros_ast) = gerar_ast_tudo(&tokens, false);
+    erros.extend(erros_ast);
+    erros.sort_by_key(|d| d.pos.as_ref().map(|p| (p.linha, p.coluna)));
+    (ast, erros)
+}
+
+// nome valido pra uma funcao definida em tempo de execucao
+fn validar_nome(nome: &str) -> Result<(), String> {
+    match tokenizar(nome, "<define>") {
+        Ok(tokens) => match tokens.as_slice() {
+            [(Token::Simbolo(s), _)] if s == nome => {
+                if palavra_builtin(nome).is_some() {
+                    Err(format!("`{}` e uma palavra reservada", nome))
+                } else {
+                    Ok(())
+                }
+            }
+            _ => Err(format!("nome de funcao invalido: {:?}", nome)),
+        },
+        Err(erro) => Err(erro.to_string()),
+    }
+}
+
+fn arg_numero(args: &[String], i: usize, flag: &str) -> usize {
+    match args.get(i).map(|n| n.parse()) {
+        Some(Ok(n)) => n,
+        _ => log_error!("{} precisa de um numero", flag),
+    }
+}
+
+// 0 desliga o limite
+fn arg_limite(args: &[String], i: usize, flag: &str) -> Option<usize> {
+    match arg_numero(args, i, flag) {
+        0 => None,
+        n => Some(n),
+    }
+}
+
+// so valida os arquivos, sem rodar, e sai com 1 se algum tiver erro
+fn run_check(arquivos: &[String], estado: &mut PSFState) {
+    if arquivos.iter().any(|arg| arg == "--regras") {
+        for regra in lint::REGRAS {
+            println!("{:<16} {}", regra.nome, regra.descricao);
+        }
+        return;
+    }
+    if arquivos.is_empty() {
+        log_error!("check precisa de pelo menos um arquivo");
+    }
+    let mut erros = 0;
+    for arquivo in arquivos {
+        match fs::read_to_string(arquivo) {
+            Ok(conteudo) => erros += estado.check_string(&conteudo, arquivo),
+            Err(erro) => {
+                Diagnostico::erro(format!("{}: {}", arquivo, erro), None).mostrar();
+                erros += 1;
+            }
+        }
+    }
+    if erros > 0 {
+        std::process::exit(1);
+    }
+}
+
+// reescreve os arquivos formatados, com --check so diz quais estao fora do padrao
+fn run_fmt(args: &[String]) {
+    let conferir = args.iter().any(|arg| arg == "--check");
+    let arquivos: Vec<&String> = args.iter().filter(|arg| *arg != "--check").collect();
+    if arquivos.is_empty() {
+        log_error!("fmt precisa de pelo menos um arquivo");
+    }
+    let mut falhou = false;
+    for arquivo in arquivos {
+        let conteudo = match fs::read_to_string(arquivo) {
+            Ok(conteudo) => conteudo,
+            Err(erro) => {
+                Diagnostico::erro(format!("{}: {}", arquivo, erro), None).mostrar();
+                falhou = true;
+                continue;
+            }
+        };
+        let formatado = match formatador::formatar(&conteudo, arquivo) {
+            Ok(formatado) => formatado,
+            Err(erros) => {
+                for erro in &erros {
+                    erro.mostrar();
+                }
+                falhou = true;
+                continue;
+            }
+        };
+        if formatado == conteudo {
+            continue;
+        }
+        if conferir {
+            log_aviso!("{} precisa ser formatado", arquivo);
+            falhou = true;
+        } else if let Err(erro) = fs::write(arquivo, formatado) {
+            Diagnostico::erro(format!("{}: {}", arquivo, erro), None).mostrar();
+            falhou = true;
+        }
+    }
+    if falhou {
+        std::process::exit(1);
+    }
+}
+
+fn print_usage() {
+    println!(
+        "[ARQUIVO | [OPCOES]*]
+check ARQUIVO...   valida sem rodar, sai com 1 se tiver erro
+check --regras     lista as regras de lint, desligue com `# lint: -regra`
+fmt [--check] ARQUIVO...   formata os arquivos, com --check so confere
+dap                servidor do debug adapter protocol no stdin, pro editor
+lsp                servidor do language server protocol no stdin, pro editor
+test [--coverage] ARQUIVO...   roda as funcoes test-*, sai com 1 se alguma falhar
+OPCOES:
+    -h help
+    -i interativo
+    --check       procura stack vazia e tipos errados sem rodar
+    --tokens      mostra os tokens sem rodar
+    --ast         mostra a arvore de cada funcao sem rodar
+    --resolved    mostra as funcoes carregadas e pra onde cada chamada vai
+    --json        junto com --tokens, --ast ou --resolved, sai em json
+    --quantum N   passos de cada tarefa antes de trocar (padrao 100)
+    --check-effects   confere os efeitos declarados ( a:numero -- b ) nas chamadas
+    --explain     roda mostrando a stack antes de cada palavra, como no exemplos/08
+    --collapse    com --explain, chamada de funcao nomeada vira uma linha so
+DEPURACAO:
+    --debug       para na primeira palavra e le comandos, h mostra a ajuda
+    --break LOCAL para na linha N, em ARQUIVO:N ou antes de chamar a funcao, pode repetir
+RASTREIO:
+    --trace         um json por linha no stderr pra cada passo executado
+    --trace-out ARQUIVO   escreve o rastreio no arquivo
+    --trace-fn NOME       so os passos dentro da funcao NOME, pode repetir
+    --trace-max N         para de rastrear depois de N passos
+PERFIL:
+    --profile       conta palavras, chamadas, tempo de cada funcao e o pico das stacks
+    --profile-out ARQUIVO   pilhas pro flamegraph (padrao perfil.folded)
+COBERTURA:
+    --coverage      linhas, citacoes e lados de if que rodaram, resumo no stderr
+    --coverage-out ARQUIVO  escreve em lcov (padrao lcov.info)
+LIMITES (0 desliga):
+    --max-stack N   itens na stack (padrao 1000)
+    --max-cont N    itens na stack de consumo (padrao 100000)
+    --fuel N        passos executados
+    --timeout MS    tempo de execucao em milissegundos
+    --max-mem B     memoria aproximada da stack e da continuacao em bytes"
+    );
+}
+
+fn print_usage_repl() {
+    println!(
+        "usagem
+    :h :help :?   print help
+    :e :exit      exit
+    :explain EXPR mostra a stack antes de cada palavra"
+    );
+}
 
 fn run(args: Vec<String>, estado: &mut PSFState) {
     let mut tem_arq: Option<String> = None;
     let mut repl = false;
+    let mut checar = false;
+    let mut fase = None;
+    let mut json = false;
+    let mut rastreio: Option<rastreio::Rastreio> = None;
+    let mut explicar = false;
+    let mut depurar = false;
+    let mut paradas = vec![];
+    let mut colapsar = false;
+    let mut perfil: Option<String> = None;
+    let mut cobertura: Option<String> = None;
     let mut i = 1;
 
+    match args.get(1).map(String::as_str) {
+        Some("check") => {
+            run_check(&args[2..], estado);
+            return;
+        }
+        Some("fmt") => {
+            run_fmt(&args[2..]);
+            return;
+        }
+        Some("dap") => {
+            dap::servir(estado);
+            return;
+        }
+        Some("lsp") => {
+            lsp::servir(estado);
+            return;
+        }
+        Some("test") => {
+            teste::testar(&args[2..], estado);
+            return;
+        }
+        _ => {}
+    }
     if args.len() == 1 {
         // print_usage();
         // return;
@@ -646,6 +2852,98 @@ fn run(args: Vec<String>, estado: &mut PSFState) {
             "-i" => {
                 repl = true;
             }
+            "--quantum" => {
+                i += 1;
+                estado.quantum = match arg_numero(&args, i, "--quantum") {
+                    0 => log_error!("--quantum precisa de um numero positivo"),
+                    n => n,
+                };
+            }
+            "--check" => {
+                checar = true;
+            }
+            "--tokens" => fase = Some(inspecao::Fase::Tokens),
+            "--ast" => fase = Some(inspecao::Fase::Ast),
+            "--resolved" => fase = Some(inspecao::Fase::Resolvido),
+            "--json" => json = true,
+            "--check-effects" => {
+                estado.verificar_efeitos = true;
+            }
+            "--debug" => depurar = true,
+            "--break" => {
+                i += 1;
+                let Some(local) = args.get(i) else {
+                    log_error!("--break precisa de uma linha ou funcao");
+                };
+                depurar = true;
+                paradas.push(depurador::Parada::ler(local));
+            }
+            "--explain" => explicar = true,
+            "--collapse" => colapsar = true,
+            "--trace" => {
+                rastreio.get_or_insert_default();
+            }
+            "--trace-out" => {
+                i += 1;
+                let Some(caminho) = args.get(i) else {
+                    log_error!("--trace-out precisa de um arquivo");
+                };
+                if let Err(erro) = rastreio.get_or_insert_default().arquivo(caminho) {
+                    log_error!("nao deu pra criar {}: {}", caminho, erro);
+                }
+            }
+            "--trace-fn" => {
+                i += 1;
+                let Some(nome) = args.get(i) else {
+                    log_error!("--trace-fn precisa de um nome de funcao");
+                };
+                rastreio.get_or_insert_default().funcoes.push(nome.clone());
+            }
+            "--trace-max" => {
+                i += 1;
+                rastreio.get_or_insert_default().maximo = Some(arg_numero(&args, i, "--trace-max"));
+            }
+            "--profile" => {
+                perfil.get_or_insert_with(|| "perfil.folded".to_owned());
+            }
+            "--profile-out" => {
+                i += 1;
+                let Some(caminho) = args.get(i) else {
+                    log_error!("--profile-out precisa de um arquivo");
+                };
+                perfil = Some(caminho.clone());
+            }
+            "--coverage" => {
+                cobertura.get_or_insert_with(|| "lcov.info".to_owned());
+            }
+            "--coverage-out" => {
+                i += 1;
+                let Some(caminho) = args.get(i) else {
+                    log_error!("--coverage-out precisa de um arquivo");
+                };
+                cobertura = Some(caminho.clone());
+            }
+            "--max-stack" => {
+                i += 1;
+                estado.limites.stack = arg_limite(&args, i, "--max-stack");
+            }
+            "--max-cont" => {
+                i += 1;
+                estado.limites.continuacao = arg_limite(&args, i, "--max-cont");
+            }
+            "--fuel" => {
+                i += 1;
+                estado.limites.passos = arg_limite(&args, i, "--fuel").map(|n| n as u64);
+            }
+            "--timeout" => {
+                i += 1;
+                estado.limites.tempo =
+                    arg_limite(&args, i, "--timeout").map(|ms| Duration::from_millis(ms as u64));
+            }
+            "--max-mem" => {
+                i += 1;
+                estado.limites.memoria = arg_limite(&args, i, "--max-mem");
+            }
             outro => {
                 if let Ok(tem) = fs::exists(outro) {
                     if tem {
@@ -658,17 +2956,61 @@ fn run(args: Vec<String>, estado: &mut PSFState) {
         }
         i += 1;
     }
+    // voltar no tempo roda de novo num estado limpo, sem os observadores deles
+    if depurar && (rastreio.is_some() || perfil.is_some() || cobertura.is_some()) {
+        log_error!("--debug nao funciona junto com --trace, --profile ou --coverage");
+    }
     if let Some(arq) = tem_arq {
         // ler arquivo
-        let conteudo = match fs::read_to_string(arq) {
+        let conteudo = match fs::read_to_string(&arq) {
             Err(erro) => {
                 println!("Erro abrindo arquivo: {}", erro);
                 return;
             }
             Ok(str) => str,
         };
-        estado.load_string(&conteudo);
-        estado.run_main();
+        if checar {
+            run_check(std::slice::from_ref(&arq), estado);
+            return;
+        }
+        if let Some(fase) = fase {
+            if inspecao::inspecionar(fase, &conteudo, &arq, &estado.funcoes, json) > 0 {
+                std::process::exit(1);
+            }
+            return;
+        }
+        estado.load_string(&conteudo, &arq);
+        if let Some(rastreio) = rastreio {
+            estado.observadores.push(Box::new(rastreio));
+        }
+        let cobertura =
+            cobertura.map(|lcov| (Rc::new(RefCell::new(cobertura::Cobertura::default())), lcov));
+        if let Some((cobertura, _)) = &cobertura {
+            estado.observadores.push(Box::new(cobertura.clone()));
+        }
+        if depurar {
+            let depurador = depurador::Depurador::new(&arq, &conteudo, paradas);
+            depurador::depurar(estado, depurador);
+            return;
+        }
+        let resultado = if explicar {
+            explicacao::explicar_main(estado, colapsar)
+        } else if let Some(arquivo) = &perfil {
+            perfil::perfilar(estado, arquivo)
+        } else {
+            estado.executar_funcao("main")
+        };
+        if let Some((cobertura, lcov)) = cobertura {
+            cobertura
+                .borrow()
+                .relatorio(&[(arq.clone(), conteudo.clone())], &lcov);
+        }
+        if let Err(erro) = resultado {
+            log_error!("{}", erro.relatorio());
+        }
+        if explicar || perfil.is_some() {
+            return;
+        }
     }
 
     if repl {
@@ -687,6 +3029,10 @@ fn run(args: Vec<String>, estado: &mut PSFState) {
                 ":e" | ":exit" => {
                     return;
                 }
+                outro if outro.starts_with(":explain") => {
+                    estado.explain_raw_string(&outro[":explain".len()..]);
+                    estado.clear_stack();
+                }
                 outro => {
                     estado.run_raw_string(outro);
                     // log_info!("stack: {:?}", estado.stack.lista);
@@ -707,3 +3053,139 @@ fn main() {
 
     run(args, &mut estado);
 }
+
+#[cfg(test)]
+mod testes {
+    use super::*;
+
+    fn simbolos(entrada: &str) -> Vec<String> {
+        let tokens = tokenizar(entrada, "<teste>").unwrap();
+        tokens
+            .into_iter()
+            .map(|(token, _)| match token {
+                Token::Simbolo(nome) => nome,
+                Token::Separador => "--".to_owned(),
+                outro => format!("{:?}", outro),
+            })
+            .collect()
+    }
+
+    fn erro(entrada: &str) -> String {
+        tokenizar(entrada, "<teste>").unwrap_err().mensagem
+    }
+
+    #[test]
+    fn traco_no_meio_do_nome() {
+        assert_eq!(simbolos("assert-eq"), ["assert-eq"]);
+        assert_eq!(simbolos("assert-stack x1-2"), ["assert-stack", "x1-2"]);
+    }
+
+    #[test]
+    fn separador_do_efeito() {
+        assert_eq!(
+            simbolos("( a b -- c )"),
+            ["ParenAbr", "a", "b", "--", "c", "ParenFec"]
+        );
+        assert_eq!(simbolos("(--)"), ["ParenAbr", "--", "ParenFec"]);
+    }
+
+    #[test]
+    fn traco_no_fim_do_nome() {
+        assert!(erro("a- b").contains("`a-`"));
+        assert!(erro("main = ( dup- )").contains("`dup-`"));
+        assert!(erro("a-").contains("`a-`"));
+    }
+
+    #[test]
+    fn dois_tracos_no_nome() {
+        assert!(erro("a--b").contains("`a--b`"));
+        assert!(erro("a-- b").contains("`a--`"));
+    }
+
+    fn resolvido(fonte: &str) -> Vec<(String, Option<String>)> {
+        let (ast, _) = analisar(fonte, "<teste>");
+        resolver(&ast, &HashMap::new())
+            .into_iter()
+            .map(|d| (d.mensagem, d.sugestao))
+            .collect()
+    }
+
+    #[test]
+    fn sugere_nome_parecido() {
+        let fonte = "dobro = ( dup + )\nmain = ( 2 dobo prnt )";
+        assert_eq!(
+            resolvido(fonte),
+            [
+                (
+                    "funcao `dobo` nao existe".to_owned(),
+                    Some("voce quis dizer `dobro`?".to_owned())
+                ),
+                (
+                    "funcao `prnt` nao existe".to_owned(),
+                    Some("voce quis dizer `print`?".to_owned())
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn nome_sem_sugestao() {
+        assert_eq!(
+            resolvido("main = ( 2 abacaxi )"),
+            [("funcao `abacaxi` nao existe".to_owned(), None)]
+        );
+        assert_eq!(sugerir_nome("xyz", &["print", "dup"]), None);
+    }
+
+    #[test]
+    fn json_ida_e_volta() {
+        let texto = r#"{"a":[1,-2,true,null],"b":"x \"y\"\n\u00e9","c":{}}"#;
+        let valor = json::Json::ler(texto).unwrap();
+        assert_eq!(valor.get("a").lista().len(), 4);
+        assert_eq!(valor.get("b").texto(), Some("x \"y\"\né"));
+        assert_eq!(json::Json::ler(&valor.to_string()).unwrap(), valor);
+        let espacado = json::Json::ler(" { \"a\" : [ 1 , 2 ] } ").unwrap();
+        assert_eq!(espacado.to_string(), r#"{"a":[1,2]}"#);
+    }
+
+    #[test]
+    fn json_invalido() {
+        for texto in [
+            "",
+            "{",
+            "[1,]",
+            "{\"a\"}",
+            "\"sem fim",
+            "tru",
+            "1 2",
+            "{\"a\":1,}",
+        ] {
+            assert!(
+                json::Json::ler(texto).is_err(),
+                "{:?} devia dar erro",
+                texto
+            );
+        }
+    }
+
+    #[test]
+    fn mensagem_ida_e_volta() {
+        let mensagem = json::Json::objeto([("seq", 1i64.into()), ("command", "next".into())]);
+        let mut saida = vec![];
+        json::escrever_mensagem(&mut saida, &mensagem).unwrap();
+        let mut entrada = io::Cursor::new(saida);
+        assert_eq!(json::ler_mensagem(&mut entrada).unwrap(), Some(mensagem));
+        assert_eq!(json::ler_mensagem(&mut entrada).unwrap(), None);
+    }
+
+    #[test]
+    fn mensagem_invalida() {
+        let ler = |texto: &str| json::ler_mensagem(&mut io::Cursor::new(texto.as_bytes()));
+        let erro = ler("Content-Length: 99999999999\r\n\r\n{}").unwrap_err();
+        assert_eq!(erro.kind(), io::ErrorKind::InvalidData);
+        let erro = ler("Content-Length: 10\r\n\r\n{}").unwrap_err();
+        assert_eq!(erro.kind(), io::ErrorKind::UnexpectedEof);
+        let erro = ler("Content-Length: 2\r\n\r\n{x").unwrap_err();
+        assert_eq!(erro.kind(), io::ErrorKind::InvalidData);
+    }
+}
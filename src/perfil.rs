@@ -0,0 +1,244 @@
+// ---------- Perfil ----------
+
+// --profile: conta as palavras executadas e as chamadas de cada funcao, o
+// tempo inclusivo (com o que ela chama) e exclusivo, o pico das duas stacks,
+// e escreve as pilhas de chamadas no formato do flamegraph (main;f;g 1234)
+
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use crate::{
+    ASTItem, ErroPSF, No, Observador, PSFState, Quadro, Stack, cadeia_chamadas, nome_builtin,
+};
+
+// um quadro da stack de consumo e as funcoes que passaram por ele em cauda,
+// a chamada primeiro
+#[derive(Debug)]
+struct Grupo {
+    nomes: Vec<String>,
+    caudas: usize,
+}
+
+#[derive(Debug, Default)]
+pub struct Perfil {
+    palavras: HashMap<&'static str, u64>,
+    chamadas: HashMap<String, u64>,
+    // tempo e passos de cada cadeia de chamadas, "main;f;g"
+    pilhas: HashMap<String, (Duration, u64)>,
+    grupos: Vec<Grupo>,
+    chave: String,
+    pico_stack: usize,
+    pico_consumir: usize,
+    // fim do ultimo passo, sem contar o tempo do proprio perfil
+    ultimo: Option<Instant>,
+}
+
+// as palavras pelo nome, o resto pelo tipo
+fn tipo_item(item: &ASTItem) -> &'static str {
+    match item {
+        ASTItem::Numero(_) => "<numero>",
+        ASTItem::String(_) => "<string>",
+        ASTItem::Valor(_) => "<valor>",
+        ASTItem::FuncDef(_) => "<citacao>",
+        ASTItem::FuncCallNamed(_) => "<chamada>",
+        ASTItem::FimTry(..) => "<fim-try>",
+        ASTItem::FimChamada(_) => "<fim-chamada>",
+        ASTItem::EachGerador(..) => "<each-gerador>",
+        outro => nome_builtin(outro).unwrap_or("?"),
+    }
+}
+
+fn ms(tempo: Duration) -> String {
+    format!("{:.3}ms", tempo.as_secs_f64() * 1000.0)
+}
+
+fn porcento(parte: Duration, total: Duration) -> f64 {
+    match total.is_zero() {
+        true => 0.0,
+        false => parte.as_secs_f64() * 100.0 / total.as_secs_f64(),
+    }
+}
+
+impl Perfil {
+    // a chamada de cauda reaproveita o quadro, entao quem ela substituiu
+    // continua na cadeia; voltar em cauda pra uma funcao do mesmo quadro fecha
+    // o ciclo em vez de crescer a cada volta. true se a cadeia mudou
+    fn acompanhar(&mut self, cadeia: &[&Quadro]) -> bool {
+        let iguais = self
+            .grupos
+            .iter()
+            .zip(cadeia)
+            .take_while(|(grupo, quadro)| {
+                grupo.caudas == quadro.caudas && grupo.nomes.last() == Some(&quadro.nome)
+            })
+            .count();
+        if iguais == cadeia.len() && iguais == self.grupos.len() {
+            return false;
+        }
+        if iguais + 1 == cadeia.len()
+            && iguais + 1 == self.grupos.len()
+            && cadeia[iguais].caudas == self.grupos[iguais].caudas + 1
+        {
+            let quadro = cadeia[iguais];
+            let grupo = &mut self.grupos[iguais];
+            grupo.caudas = quadro.caudas;
+            return match grupo.nomes.iter().position(|nome| *nome == quadro.nome) {
+                Some(i) if i + 1 == grupo.nomes.len() => false,
+                Some(i) => {
+                    grupo.nomes.truncate(i + 1);
+                    true
+                }
+                None => {
+                    grupo.nomes.push(quadro.nome.clone());
+                    true
+                }
+            };
+        }
+        // retorno, chamada nova, erro ou continuacao: o que nao bate vem da stack
+        self.grupos.truncate(iguais);
+        self.grupos
+            .extend(cadeia[iguais..].iter().map(|quadro| Grupo {
+                nomes: vec![quadro.nome.clone()],
+                caudas: quadro.caudas,
+            }));
+        true
+    }
+
+    // o tempo desde o ultimo passo e da cadeia onde ele rodou
+    fn contar_tempo(&mut self) {
+        let Some(ultimo) = self.ultimo.take() else {
+            return;
+        };
+        // sem alocar a chave a cada passo
+        let pilha = match self.pilhas.get_mut(&self.chave) {
+            Some(pilha) => pilha,
+            None => self.pilhas.entry(self.chave.clone()).or_default(),
+        };
+        pilha.0 += ultimo.elapsed();
+        pilha.1 += 1;
+    }
+
+    fn escrever_pilhas(&self, arquivo: &str) {
+        let mut pilhas: Vec<_> = self.pilhas.iter().collect();
+        pilhas.sort_by(|a, b| a.0.cmp(b.0));
+        let texto: String = pilhas
+            .iter()
+            .filter(|(_, (tempo, _))| !tempo.is_zero())
+            .map(|(chave, (tempo, _))| format!("{} {}\n", chave, tempo.as_nanos()))
+            .collect();
+        match fs::write(arquivo, texto) {
+            Ok(()) => eprintln!("pilhas pro flamegraph em {}", arquivo),
+            Err(erro) => eprintln!("nao deu pra escrever {}: {}", arquivo, erro),
+        }
+    }
+
+    fn relatorio(&self) {
+        let total: Duration = self.pilhas.values().map(|(tempo, _)| *tempo).sum();
+        let passos: u64 = self.pilhas.values().map(|(_, passos)| passos).sum();
+        let mut inclusivo: HashMap<&str, Duration> = HashMap::new();
+        let mut exclusivo: HashMap<&str, Duration> = HashMap::new();
+        for (chave, (tempo, _)) in &self.pilhas {
+            // recursao aparece varias vezes na cadeia mas conta uma
+            let nomes: HashSet<&str> = chave.split(';').collect();
+            for nome in nomes {
+                *inclusivo.entry(nome).or_default() += *tempo;
+            }
+            let dono = chave.rsplit(';').next().unwrap_or(chave);
+            *exclusivo.entry(dono).or_default() += *tempo;
+        }
+
+        eprintln!("perfil: {} passos em {}", passos, ms(total));
+        eprintln!(
+            "pico da stack: {} itens, da stack de consumo: {} itens",
+            self.pico_stack, self.pico_consumir
+        );
+
+        let mut funcoes: Vec<(&str, Duration)> = inclusivo.into_iter().collect();
+        funcoes.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        let largura = funcoes
+            .iter()
+            .map(|(nome, _)| nome.len())
+            .max()
+            .unwrap_or(0);
+        let largura = largura.max("funcao".len());
+        eprintln!();
+        eprintln!(
+            "{:<largura$}  {:>10}  {:>20}  {:>20}",
+            "funcao", "chamadas", "inclusivo", "exclusivo"
+        );
+        for (nome, tempo) in funcoes {
+            let proprio = exclusivo.get(nome).copied().unwrap_or_default();
+            eprintln!(
+                "{:<largura$}  {:>10}  {:>12} {:>6.1}%  {:>12} {:>6.1}%",
+                nome,
+                self.chamadas.get(nome).copied().unwrap_or(0),
+                ms(tempo),
+                porcento(tempo, total),
+                ms(proprio),
+                porcento(proprio, total)
+            );
+        }
+
+        let mut palavras: Vec<(&str, u64)> = self.palavras.iter().map(|(p, n)| (*p, *n)).collect();
+        palavras.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        let largura = palavras.iter().map(|(p, _)| p.len()).max().unwrap_or(0);
+        let largura = largura.max("palavra".len());
+        eprintln!();
+        eprintln!("{:<largura$}  {:>10}", "palavra", "vezes");
+        for (palavra, vezes) in palavras {
+            eprintln!("{:<largura$}  {:>10}", palavra, vezes);
+        }
+    }
+}
+
+impl Observador for Rc<RefCell<Perfil>> {
+    fn passo(
+        &mut self,
+        estado: &mut PSFState,
+        no: &No,
+        stack_consumir: &Stack<No>,
+    ) -> Result<(), ErroPSF> {
+        let mut perfil = self.borrow_mut();
+        perfil.contar_tempo();
+
+        *perfil.palavras.entry(tipo_item(&no.item)).or_default() += 1;
+        if let ASTItem::FuncCallNamed(nome) = &no.item {
+            *perfil.chamadas.entry(nome.clone()).or_default() += 1;
+        }
+        perfil.pico_stack = perfil.pico_stack.max(estado.stack.len());
+        perfil.pico_consumir = perfil.pico_consumir.max(stack_consumir.lista.len());
+
+        // a chave so e montada de novo quando a cadeia muda
+        if perfil.acompanhar(&cadeia_chamadas(stack_consumir)) {
+            let nomes: Vec<&str> = perfil
+                .grupos
+                .iter()
+                .flat_map(|grupo| &grupo.nomes)
+                .map(String::as_str)
+                .collect();
+            perfil.chave = nomes.join(";");
+        }
+        // antes da main e depois dela o tempo nao e de nenhuma funcao
+        if !perfil.grupos.is_empty() {
+            perfil.ultimo = Some(Instant::now());
+        }
+        Ok(())
+    }
+}
+
+// roda a main com o perfil e mostra o relatorio no stderr, mesmo com erro
+pub fn perfilar(estado: &mut PSFState, arquivo: &str) -> Result<(), ErroPSF> {
+    let perfil = Rc::new(RefCell::new(Perfil::default()));
+    estado.observadores.push(Box::new(perfil.clone()));
+    let resultado = estado.executar_funcao("main");
+    estado.observadores.pop();
+
+    let mut perfil = perfil.borrow_mut();
+    perfil.contar_tempo();
+    perfil.relatorio();
+    perfil.escrever_pilhas(arquivo);
+    resultado
+}
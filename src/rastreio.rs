@@ -0,0 +1,100 @@
+// ---------- Rastreio ----------
+
+// --trace: um objeto json por linha pra cada passo, com a palavra, onde ela
+// esta no arquivo, a profundidade de chamadas e a stack antes de rodar
+
+use std::fs::File;
+use std::io::{self, LineWriter, Write};
+
+use crate::json::Json;
+use crate::{
+    ASTItem, ErroPSF, Item, No, Observador, PSFState, Stack, ast_para_item, cadeia_chamadas,
+    formatar_itens, profundidade,
+};
+
+#[derive(Debug, Default)]
+pub struct Rastreio {
+    // None e o stderr
+    saida: Option<LineWriter<File>>,
+    // vazio rastreia todas
+    pub funcoes: Vec<String>,
+    pub maximo: Option<usize>,
+    escritos: usize,
+}
+
+impl Rastreio {
+    pub fn arquivo(&mut self, caminho: &str) -> io::Result<()> {
+        self.saida = Some(LineWriter::new(File::create(caminho)?));
+        Ok(())
+    }
+
+    fn escrever(&mut self, linha: String) {
+        // erro de escrita nao para o programa, o rastreio so fica incompleto
+        let _ = match &mut self.saida {
+            Some(arquivo) => writeln!(arquivo, "{}", linha),
+            None => writeln!(io::stderr(), "{}", linha),
+        };
+    }
+}
+
+// a palavra como ela aparece no codigo, os internos com um nome proprio
+pub fn palavra(item: &ASTItem) -> String {
+    match item {
+        ASTItem::FimTry(..) => "fim-try".to_owned(),
+        ASTItem::FimChamada(quadro) => format!("fim-chamada {}", quadro.nome),
+        ASTItem::EachGerador(..) => "each-gerador".to_owned(),
+        outro => formatar_itens(&[ast_para_item(outro)]),
+    }
+}
+
+// marcadores da stack de consumo, nao sao palavras do programa
+pub fn interno(item: &ASTItem) -> bool {
+    matches!(
+        item,
+        ASTItem::FimTry(..) | ASTItem::FimChamada(_) | ASTItem::EachGerador(..)
+    )
+}
+
+fn item_json(item: &Item) -> Json {
+    match item {
+        Item::Bool(b) => Json::Bool(*b),
+        Item::Numero(n) => Json::Numero(*n as i64),
+        Item::String(s) => s.as_str().into(),
+        Item::Lista(itens) => Json::Lista(itens.iter().map(item_json).collect()),
+        outro => outro.to_string().into(),
+    }
+}
+
+impl Observador for Rastreio {
+    fn passo(
+        &mut self,
+        estado: &mut PSFState,
+        no: &No,
+        stack_consumir: &Stack<No>,
+    ) -> Result<(), ErroPSF> {
+        if self.maximo.is_some_and(|maximo| self.escritos >= maximo) {
+            return Ok(());
+        }
+        let cadeia = cadeia_chamadas(stack_consumir);
+        let funcao = cadeia.last().map(|quadro| quadro.nome.as_str());
+        if !self.funcoes.is_empty() && !funcao.is_some_and(|f| self.funcoes.iter().any(|n| n == f))
+        {
+            return Ok(());
+        }
+        self.escritos += 1;
+        let registro = Json::objeto([
+            ("passo", Json::Numero(estado.passos as i64)),
+            ("tarefa", estado.tarefa_atual.into()),
+            ("palavra", palavra(&no.item).into()),
+            ("pos", no.pos.as_deref().into()),
+            ("profundidade", profundidade(&cadeia).into()),
+            ("funcao", funcao.into()),
+            (
+                "stack",
+                Json::Lista(estado.stack.lista.iter().map(item_json).collect()),
+            ),
+        ]);
+        self.escrever(registro.to_string());
+        Ok(())
+    }
+}
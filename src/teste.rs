@@ -0,0 +1,149 @@
+// ---------- Teste ----------
+
+// `test ARQUIVO...`: roda cada definicao com nome comecando em `test-` num
+// estado novo, com a saida capturada e o input sem nada pra ler, e sai com 1
+// se algum falhar. Os asserts lancam erros do tipo "assert".
+//
+// test-dobro = ( 4 dobro 8 assert-eq )
+
+use std::cell::RefCell;
+use std::fs;
+use std::rc::Rc;
+
+use crate::cobertura::Cobertura;
+use crate::{Diagnostico, Entradas, Nivel, PSFState, Saida, analisar, resolver};
+
+pub const PREFIXO: &str = "test-";
+
+#[derive(Debug, Default)]
+struct Placar {
+    passaram: usize,
+    falharam: usize,
+}
+
+// texto com recuo, pra ficar embaixo do nome do teste
+fn recuado(texto: &str) -> String {
+    texto
+        .lines()
+        .map(|linha| format!("    {}", linha))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn testar_arquivo(
+    base: &PSFState,
+    arquivo: &str,
+    conteudo: &str,
+    cobertura: Option<&Rc<RefCell<Cobertura>>>,
+    placar: &mut Placar,
+) {
+    let (ast, mut diagnosticos) = analisar(conteudo, arquivo);
+    if diagnosticos.is_empty() {
+        diagnosticos = resolver(&ast, &base.funcoes);
+    }
+    diagnosticos.retain(|d| d.nivel == Nivel::Erro);
+    if !diagnosticos.is_empty() {
+        for diagnostico in &diagnosticos {
+            diagnostico.mostrar();
+        }
+        placar.falharam += 1;
+        return;
+    }
+    let testes: Vec<&str> = ast
+        .iter()
+        .map(|definicao| definicao.nome.as_str())
+        .filter(|nome| nome.starts_with(PREFIXO))
+        .collect();
+    if testes.is_empty() {
+        println!("{}: nenhuma funcao comecando com `{}`", arquivo, PREFIXO);
+    }
+    for nome in testes {
+        let mut estado = base.recomecar();
+        estado.load_ast(ast.clone());
+        estado.saida = Saida::Capturada(String::new());
+        estado.entradas = Some(Entradas {
+            fechada: true,
+            ..Entradas::default()
+        });
+        if let Some(cobertura) = cobertura {
+            estado.observadores.push(Box::new(cobertura.clone()));
+        }
+        match estado.executar_funcao(nome) {
+            Ok(()) => {
+                println!("\x1b[1;32mok\x1b[0m      {} {}", arquivo, nome);
+                placar.passaram += 1;
+            }
+            Err(erro) => {
+                println!("\x1b[1;31mfalhou\x1b[0m  {} {}", arquivo, nome);
+                println!("{}", recuado(&erro.relatorio()));
+                if let Saida::Capturada(saida) = &estado.saida
+                    && !saida.is_empty()
+                {
+                    println!("    saida:");
+                    println!("{}", recuado(&recuado(saida)));
+                }
+                placar.falharam += 1;
+            }
+        }
+    }
+}
+
+pub fn testar(args: &[String], base: &PSFState) {
+    let mut arquivos = vec![];
+    let mut lcov: Option<String> = None;
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--coverage" => {
+                lcov.get_or_insert_with(|| "lcov.info".to_owned());
+            }
+            "--coverage-out" => {
+                i += 1;
+                let Some(caminho) = args.get(i) else {
+                    Diagnostico::erro("--coverage-out precisa de um arquivo".to_owned(), None)
+                        .mostrar();
+                    std::process::exit(1);
+                };
+                lcov = Some(caminho.clone());
+            }
+            arquivo => arquivos.push(arquivo.to_owned()),
+        }
+        i += 1;
+    }
+    if arquivos.is_empty() {
+        Diagnostico::erro("test precisa de pelo menos um arquivo".to_owned(), None).mostrar();
+        std::process::exit(1);
+    }
+
+    let cobertura = lcov
+        .as_ref()
+        .map(|_| Rc::new(RefCell::new(Cobertura::default())));
+    let mut placar = Placar::default();
+    let mut fontes = vec![];
+    for arquivo in arquivos {
+        let conteudo = match fs::read_to_string(&arquivo) {
+            Ok(conteudo) => conteudo,
+            Err(erro) => {
+                Diagnostico::erro(format!("{}: {}", arquivo, erro), None).mostrar();
+                placar.falharam += 1;
+                continue;
+            }
+        };
+        testar_arquivo(base, &arquivo, &conteudo, cobertura.as_ref(), &mut placar);
+        fontes.push((arquivo, conteudo));
+    }
+
+    println!();
+    println!(
+        "{} teste(s): {} ok, {} falharam",
+        placar.passaram + placar.falharam,
+        placar.passaram,
+        placar.falharam
+    );
+    if let (Some(cobertura), Some(lcov)) = (cobertura, lcov) {
+        cobertura.borrow().relatorio(&fontes, &lcov);
+    }
+    if placar.falharam > 0 {
+        std::process::exit(1);
+    }
+}